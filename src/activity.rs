@@ -0,0 +1,76 @@
+
+use crate::{
+	UserId,
+	field::Field,
+	errors::ParseError,
+	parse_err,
+	utils::{percent_encode, percent_decode}
+};
+
+/// The last turn each player submitted valid commands
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Activity {
+	last_active: Vec<(UserId, u64)>
+}
+
+impl Activity {
+
+	pub fn last_active(&self, user: &UserId) -> Option<u64> {
+		self.last_active.iter().find(|(u, _)| u == user).map(|(_, turn)| *turn)
+	}
+
+	pub fn record(&mut self, user: &UserId, turn: u64) {
+		match self.last_active.iter_mut().find(|(u, _)| u == user) {
+			Some((_, last)) => *last = turn,
+			None => self.last_active.push((user.clone(), turn))
+		}
+	}
+
+	/// Start tracking the players that own plots but were not seen yet, like players from before tracking existed
+	pub fn track(&mut self, field: &Field, turn: u64) {
+		for keep in field.list_keeps() {
+			if let Some(owner) = field.plot_owner(keep) {
+				if self.last_active(&owner).is_none() {
+					self.last_active.push((owner, turn));
+				}
+			}
+		}
+	}
+
+	/// The players that have not been active for `limit` turns. The barbarians never are
+	pub fn idle(&self, turn: u64, limit: u64) -> Vec<UserId> {
+		self.last_active.iter()
+			.filter(|(user, last)| !user.is_barbarians() && turn.saturating_sub(*last) >= limit)
+			.map(|(user, _)| user.clone())
+			.collect()
+	}
+
+	pub fn forget(&mut self, user: &UserId) {
+		self.last_active.retain(|(u, _)| u != user);
+	}
+
+	pub fn rename(&mut self, old: &UserId, new: &UserId) {
+		for (user, _) in self.last_active.iter_mut() {
+			if user == old {
+				*user = new.clone();
+			}
+		}
+	}
+
+	/// Items for the meta section of the world file
+	pub fn to_meta(&self) -> Vec<String> {
+		self.last_active.iter().map(|(user, turn)| format!("active:{},{}", percent_encode(&user.0), turn)).collect()
+	}
+
+	/// Read an item from the meta section. Returns false if the item is not about activity
+	pub fn parse_meta(&mut self, name: &str, arg: &str) -> Result<bool, ParseError> {
+		if name != "active" {
+			return Ok(false);
+		}
+		let (user, turn) = arg.rsplit_once(',').ok_or_else(|| parse_err!("Invalid active '{}'", arg))?;
+		let user = percent_decode(user.trim());
+		let turn = turn.trim().parse().map_err(|_| parse_err!("Invalid turn '{}'", turn))?;
+		self.last_active.push((UserId(user), turn));
+		Ok(true)
+	}
+}
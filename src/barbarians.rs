@@ -0,0 +1,87 @@
+
+use crate::{
+	UserId,
+	field::Field,
+	entity::Entity,
+	resources::Resource,
+	commands::{Command, Action, Order},
+	locations::Direction,
+	utils,
+	Pos
+};
+
+const LOOT: &[Resource] = &[Resource::Wood, Resource::Stone, Resource::Food, Resource::Iron];
+
+/// Give `count` free plots to the barbarians, with walls around the keep, warriors and loot.
+/// Which plots is decided by `random_seed`
+pub fn seed(field: &mut Field, count: usize, random_seed: u32) {
+	let mut free: Vec<Pos> = field.list_keeps().into_iter().filter(|keep| field.get(*keep).is_none()).collect();
+	free.sort_by_key(|keep| utils::randomize((keep.x + keep.y * 67679) as u32 ^ random_seed));
+	for keep in free.into_iter().take(count) {
+		field.set_tile(keep, Entity::Keep(UserId::barbarians()));
+		let mut place = |offset: Pos, ent: Entity| {
+			let pos = keep + offset;
+			if field.keep_location(pos) == keep && field.get(pos).is_none() {
+				field.set_tile(pos, ent);
+			}
+		};
+		for dx in -1..=1 {
+			for dy in -1..=1 {
+				place(Pos::new(dx, dy), Entity::Wall);
+			}
+		}
+		for dir in Direction::directions() {
+			place(dir.to_pos() * 2, Entity::Warrior);
+		}
+		for (offset, res) in [Pos::new(-2, -2), Pos::new(2, -2), Pos::new(-2, 2), Pos::new(2, 2)].iter().zip(LOOT) {
+			place(*offset, Entity::Stockpile(Some(*res)));
+		}
+	}
+}
+
+/// The orders of the barbarians for this turn: every warrior attacks units in a neighbouring plot of a player
+pub fn orders(field: &Field) -> Vec<Order> {
+	let mut orders = Vec::new();
+	for keep in field.list_keeps() {
+		if !field.plot_owner(keep).is_some_and(|owner| owner.is_barbarians()) {
+			continue;
+		}
+		for pos in field.tiles_in_plot(keep) {
+			if field.get(pos) != Some(Entity::Warrior) {
+				continue;
+			}
+			let target = Direction::directions().into_iter().find(|dir| {
+				let lane = field.neighbour_lane(pos, dir.clone());
+				let hostile = lane.first().and_then(|p| field.plot_owner(*p)).is_some_and(|owner| !owner.is_barbarians());
+				hostile && lane.iter().any(|p| field.get(*p).is_some_and(|ent| ent.properties().mortal))
+			});
+			if let Some(dir) = target {
+				orders.push(Order::Command(Command{pos: pos.into(), action: Action::Attack(dir), condition: None}));
+			}
+		}
+	}
+	orders
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::str::FromStr;
+
+	#[test]
+	fn test_barbarians() {
+		let mut field = Field::from_str("size:2,1; plot_size:10,10;; 5,5 capital:alice; 3,4 raider;").unwrap();
+		seed(&mut field, 3, 0);
+		let keep = Pos::new(15, 4);
+		assert_eq!(field.plot_owner(keep), Some(UserId::barbarians()));
+		assert_eq!(field.get(keep + Pos::new(1, 1)), Some(Entity::Wall));
+		assert_eq!(field.get(keep + Pos::new(-2, 0)), Some(Entity::Warrior));
+		assert_eq!(field.get(keep + Pos::new(2, 2)), Some(Entity::Stockpile(Some(Resource::Iron))));
+		assert_eq!(field.plot_owner(Pos::new(5, 5)), Some(UserId("alice".to_string())));
+
+		// Only the warriors in the same row as the raider can reach it
+		let orders = orders(&field);
+		assert_eq!(orders, vec![Order::from_str("13,4 attack west").unwrap(), Order::from_str("17,4 attack west").unwrap()]);
+	}
+}
@@ -0,0 +1,279 @@
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{
+	UserId,
+	field::Field,
+	entity::Entity,
+	buildings::BuildingType,
+	resources::ResourceCount,
+	commands::{Command, Action},
+	locations::Direction,
+	rules,
+	errors::ParseError,
+	parse_err,
+	Pos
+};
+
+/// The number of stockpiles a bot keeps in its capital plot, enough to save for taking over a plot
+const STOCKPILES: usize = 24;
+/// The number of each production building a bot builds in its capital plot
+const PRODUCERS: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+	/// Produce and take over every free plot nearby
+	Economic,
+	/// Produce and train warriors to defend
+	Turtle,
+	/// Train raiders and attack the neighbours
+	Raider
+}
+
+impl FromStr for Strategy {
+	type Err = ParseError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.trim() {
+			"economic" => Ok(Self::Economic),
+			"turtle" => Ok(Self::Turtle),
+			"raider" => Ok(Self::Raider),
+			s => Err(ParseError::invalid_word("strategy", s, &["economic", "turtle", "raider"]))
+		}
+	}
+}
+
+impl fmt::Display for Strategy {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", match self {
+			Self::Economic => "economic",
+			Self::Turtle => "turtle",
+			Self::Raider => "raider"
+		})
+	}
+}
+
+/// A player that is played by a bot, written as "<player>:<strategy>"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bot {
+	pub user: UserId,
+	pub strategy: Strategy
+}
+
+impl FromStr for Bot {
+	type Err = ParseError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (user, strategy) = s.rsplit_once(':').ok_or_else(|| parse_err!("Expected '<player>:<strategy>'. Found '{}'", s))?;
+		UserId::validate(user)?;
+		Ok(Self{user: UserId(user.to_string()), strategy: Strategy::from_str(strategy)?})
+	}
+}
+
+/// The commands that are planned so far, with the field as it will be after them
+struct Plan<'a> {
+	field: Field,
+	user: &'a UserId,
+	commands: Vec<Command>
+}
+
+impl Plan<'_> {
+
+	fn add(&mut self, pos: Pos, action: Action) {
+		self.commands.push(Command{pos: pos.into(), action, condition: None});
+	}
+
+	fn keeps(&self) -> Vec<Pos> {
+		self.field.list_keeps().into_iter().filter(|keep| self.field.plot_owner(*keep).as_ref() == Some(self.user)).collect()
+	}
+
+	fn count(&self, keep: Pos, ent: Entity) -> usize {
+		self.field.find_all(keep, Some(ent)).len()
+	}
+
+	/// Take the resources from the plan, so later commands don't count on them
+	fn spend(&mut self, keep: Pos, cost: &ResourceCount) -> bool {
+		rules::pay(&mut self.field, keep, cost)
+	}
+
+	/// Build on the first empty tile of the plot that satisfies `suitable`
+	fn build(&mut self, keep: Pos, building: BuildingType, suitable: impl Fn(&Field, Pos) -> bool) -> bool {
+		let pos = match self.field.find_all(keep, None).into_iter().find(|pos| suitable(&self.field, *pos)) {
+			Some(pos) => pos,
+			None => return false
+		};
+		let (cost, _) = building.cost_result();
+		if !self.spend(keep, &cost) {
+			return false;
+		}
+		self.field.set_tile(pos, Entity::Construction(building.clone()));
+		self.add(pos, Action::Build(building));
+		true
+	}
+
+	/// Use every production building that still has an empty stockpile to fill
+	fn produce(&mut self) {
+		for keep in self.keeps() {
+			let mut free = rules::free_storage(&self.field, keep);
+			for pos in self.field.tiles_in_plot(keep) {
+				if free == 0 {
+					break;
+				}
+				if matches!(self.field.get(pos), Some(Entity::Woodcutter) | Some(Entity::Farm) | Some(Entity::Quarry)) {
+					self.add(pos, Action::Use);
+					free -= 1;
+				}
+			}
+		}
+	}
+
+	fn develop(&mut self, capital: Pos) {
+		let stockpiles = self.count(capital, Entity::Stockpile(None)) + rules::available_resources(&self.field, capital).to_vec().len();
+		// A few at a time, so there are actions left for the rest
+		for _ in stockpiles..STOCKPILES.min(stockpiles + 3) {
+			self.build(capital, BuildingType::Stockpile, |_, _| true);
+		}
+		if self.count(capital, Entity::Woodcutter) < PRODUCERS {
+			self.build(capital, BuildingType::Woodcutter, |field, pos| field.neighbours(pos, Some(Entity::Forest)));
+		}
+		if self.count(capital, Entity::Farm) < PRODUCERS {
+			self.build(capital, BuildingType::Farm, |_, _| true);
+		}
+	}
+
+	/// Take over a free plot next to the capital with a scout post
+	fn expand(&mut self, capital: Pos, takeover_cost: &ResourceCount) {
+		let user = self.user.clone();
+		let free = |field: &Field, pos: Pos| field.across_border(pos)
+			.filter(|target| field.is_valid(*target) && field.get(field.keep_location(*target)).is_none())
+			.is_some_and(|target| !field.tiles_in_plot(target).into_iter().filter_map(|p| field.get(p)).any(|ent| ent.properties().defender))
+			&& field.plot_owner(pos) == Some(user.clone());
+		let scoutpost = self.field.find_all(capital, Some(Entity::Scoutpost)).into_iter().find(|pos| free(&self.field, *pos));
+		match scoutpost {
+			Some(pos) => {
+				if self.spend(capital, takeover_cost) {
+					self.add(pos, Action::Use);
+				}
+			}
+			None => {
+				self.build(capital, BuildingType::Scoutpost, free);
+			}
+		}
+	}
+
+	/// Use a training building as often as there are resources for it
+	fn train(&mut self, capital: Pos, building: Entity) {
+		if let (Some(cost), Some(pos)) = (rules::training_cost(&building), self.field.find(capital, Some(building.clone()))) {
+			if self.spend(capital, &cost) {
+				self.add(pos, Action::Use);
+			}
+		}
+	}
+
+	/// Let every raider attack a neighbouring plot of another player where it can destroy something
+	fn raid(&mut self) {
+		for keep in self.keeps() {
+			for pos in self.field.find_all(keep, Some(Entity::Raider)) {
+				let target = Direction::directions().into_iter().find(|dir| {
+					let lane = self.field.neighbour_lane(pos, dir.clone());
+					let hostile = lane.first().and_then(|p| self.field.plot_owner(*p)).is_some_and(|owner| &owner != self.user);
+					hostile && lane.iter().filter_map(|p| self.field.get(*p)).any(|ent| {
+						let props = ent.properties();
+						props.destructible && !props.strong
+					})
+				});
+				if let Some(dir) = target {
+					self.add(pos, Action::Attack(dir));
+				}
+			}
+		}
+	}
+}
+
+/// The commands of a bot for the next turn, in a world where taking over a plot costs `takeover_cost`
+pub fn commands(field: &Field, user: &UserId, strategy: Strategy, takeover_cost: &ResourceCount) -> Vec<Command> {
+	let mut plan = Plan{field: field.clone(), user, commands: Vec::new()};
+	let keeps = plan.keeps();
+	let capital = match keeps.iter().find(|keep| field.get(**keep) == Some(Entity::Capital(user.clone()))) {
+		Some(capital) => *capital,
+		None => {
+			// Claim the first plot that can be claimed
+			let claimable = field.list_keeps().into_iter().find(|keep| rules::claim_first_keep(&mut field.clone(), *keep, user.clone()).is_some());
+			if let Some(keep) = claimable {
+				plan.add(keep, Action::Claim);
+			}
+			return plan.commands;
+		}
+	};
+	plan.produce();
+	plan.develop(capital);
+	match strategy {
+		Strategy::Economic => {
+			if plan.count(capital, Entity::Quarry) == 0 {
+				plan.build(capital, BuildingType::Quarry, |field, pos| field.neighbours(pos, Some(Entity::Rock)));
+			}
+			plan.expand(capital, takeover_cost);
+		}
+		Strategy::Turtle => {
+			if plan.count(capital, Entity::Barracks) == 0 {
+				plan.build(capital, BuildingType::Barracks, |_, _| true);
+			}
+			plan.train(capital, Entity::Barracks);
+		}
+		Strategy::Raider => {
+			if plan.count(capital, Entity::Lair) == 0 {
+				plan.build(capital, BuildingType::Lair, |_, _| true);
+			}
+			plan.train(capital, Entity::Lair);
+			plan.raid();
+		}
+	}
+	plan.commands.truncate(10);
+	plan.commands
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::trade;
+
+	fn user(name: &str) -> UserId {
+		UserId(name.to_string())
+	}
+
+	#[test]
+	fn test_bots() {
+		let field = Field::from_str("size:2,1; plot_size:10,10;; 15,4 capital:alice; 3,3 forest;").unwrap();
+		let bob = user("bob");
+		let cost = trade::total(&rules::default_takeover_cost());
+		let claim = commands(&field, &bob, Strategy::Economic, &cost);
+		assert_eq!(claim, vec![Command::from_str("5,5 claim").unwrap()]);
+
+		let mut field = field.clone();
+		field.set_tile(Pos::new(5, 5), Entity::Capital(bob.clone()));
+		let start = commands(&field, &bob, Strategy::Turtle, &cost);
+		assert_eq!(start.iter().filter(|c| c.action == Action::Build(BuildingType::Stockpile)).count(), 3);
+		assert!(start.iter().any(|c| c.action == Action::Build(BuildingType::Woodcutter)));
+
+		let field = Field::from_str(
+			"size:2,1; plot_size:10,10;;
+			5,5 capital:bob;
+			6,5 farm;
+			6,6 stockpile; 6,7 stockpile:food; 6,8 stockpile:food; 6,9 stockpile:food; 7,6 stockpile:wood; 7,7 stockpile:wood; 7,8 stockpile:wood;
+			8,8 lair;
+			9,4 raider;
+			15,4 capital:alice;
+			13,4 farm;"
+		).unwrap();
+		let raid = commands(&field, &bob, Strategy::Raider, &cost);
+		assert_eq!(raid[0], Command::from_str("6,5 use").unwrap());
+		assert!(raid.contains(&Command::from_str("8,8 use").unwrap()));
+		assert!(raid.contains(&Command::from_str("9,4 attack east").unwrap()));
+
+		assert_eq!(Bot::from_str("carol the bot:turtle"), Ok(Bot{user: user("carol the bot"), strategy: Strategy::Turtle}));
+		assert!(Bot::from_str("carol").is_err());
+		assert!(Bot::from_str("carol:sneaky").is_err());
+	}
+}
@@ -7,8 +7,7 @@ use std::str::FromStr;
 use crate::{
 	entity::Entity,
 	resources::{Resource, ResourceCount},
-	errors::ParseError,
-	parse_err
+	errors::ParseError
 };
 
 #[derive(Debug, PartialEq, Eq, Clone, Hash, Display, EnumIter)]
@@ -19,6 +18,7 @@ pub enum BuildingType {
 	Quarry,
 // 	Guardpost,
 	Stockpile,
+	Warehouse,
 	Lair,
 	Barracks,
 	Road,
@@ -37,6 +37,7 @@ impl BuildingType {
 			Self::Farm => (vec![Wood], Entity::Farm),
 			Self::Quarry => (vec![Wood, Wood, Wood, Wood], Entity::Quarry),
 			Self::Stockpile => (vec![], Entity::Stockpile(None)),
+			Self::Warehouse => (vec![Wood, Wood, Wood, Wood, Wood, Wood, Stone, Stone], Entity::Warehouse(Vec::new())),
 			Self::Lair => (vec![Wood, Wood, Wood], Entity::Lair),
 			Self::Barracks => (vec![Wood, Wood, Wood, Wood, Stone, Stone, Stone], Entity::Barracks),
 			Self::Road => (vec![Wood, Stone], Entity::Road),
@@ -59,7 +60,7 @@ impl FromStr for BuildingType {
 				return Ok(building);
 			}
 		}
-		Err(parse_err!("Invalid building '{}'", s))
+		Err(ParseError::invalid_word("building", s, &BuildingType::iter().map(|b| b.to_string()).collect::<Vec<String>>()))
 // 		Ok(match s.to_lowercase().as_str() {
 // 			"woodcutter" => Self::Woodcutter,
 // 			"farm" => Self::Farm,
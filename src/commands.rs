@@ -1,55 +1,319 @@
 use std::str::FromStr;
+use std::fmt;
 
 use crate::{
 	Pos,
+	Size,
+	UserId,
 	locations::Direction,
 	partition,
+	utils::{partition_by, offset_in},
 	errors::ParseError,
 	parse_err,
-	buildings::BuildingType
+	buildings::BuildingType,
+	entity::Entity,
+	field::Field,
+	conditions::Condition,
+	trade::Trade,
+	user::PlayerRegistry
 };
 
-#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+/// A position as written in a command.
+/// Either an absolute position, or a tile relative to the top left corner of a plot, written as "@plot tile".
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Coord {
+	Absolute(Pos),
+	Local{plot: Pos, tile: Pos}
+}
+
+impl Coord {
+	/// Returns None for local coordinates that are outside their plot
+	pub fn resolve(&self, plot_size: Size) -> Option<Pos> {
+		match self {
+			Self::Absolute(pos) => Some(*pos),
+			Self::Local{plot, tile} => {
+				if tile.x < 0 || tile.y < 0 || tile.x >= plot_size.x || tile.y >= plot_size.y {
+					return None;
+				}
+				Some(*plot * plot_size + *tile)
+			}
+		}
+	}
+	
+	/// Split a coordinate from the start of a string
+	fn partition(s: &str) -> (&str, &str) {
+		if s.starts_with('@') {
+			let (_, rest) = partition(s);
+			let (tile, rest) = partition(rest);
+			let end = offset_in(s, tile).unwrap_or(s.len()) + tile.len();
+			(&s[..end], rest)
+		} else {
+			partition(s)
+		}
+	}
+}
+
+impl From<Pos> for Coord {
+	fn from(pos: Pos) -> Self {
+		Self::Absolute(pos)
+	}
+}
+
+impl FromStr for Coord {
+	type Err = ParseError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		if let Some(local) = s.strip_prefix('@') {
+			let (plot, tile) = partition(local);
+			Ok(Self::Local{plot: Pos::from_str(plot)?, tile: Pos::from_str(tile)?})
+		} else {
+			Ok(Self::Absolute(Pos::from_str(s)?))
+		}
+	}
+}
+
+impl fmt::Display for Coord {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Absolute(pos) => write!(f, "{}", pos),
+			Self::Local{plot, tile} => write!(f, "@{} {}", plot, tile)
+		}
+	}
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Command {
-	pub pos: Pos,
-	pub action: Action
+	pub pos: Coord,
+	pub action: Action,
+	/// The command is only executed when this holds
+	pub condition: Option<Condition>
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub enum Action{
 	Claim,
 	Build(BuildingType),
-	Move(Pos),
+	Move(Coord),
 	Attack(Direction),
 	Remove,
 	Use,
+	/// Move the capital to another keep of the same player
+	Relocate(Coord),
 }
 
 
+const ACTIONS: &[&str] = &["build", "move", "attack", "remove", "use", "claim", "relocate"];
+
 impl FromStr for Action {
 	type Err = ParseError;
 
 	fn from_str(s: &str) -> Result<Self, Self::Err> {
 		let (commtype, arg) = partition(s);
 		Ok(match commtype.to_lowercase().as_str() {
-			"build" => Self::Build(BuildingType::from_str(&arg)?),
-			"move" => Self::Move(Pos::from_str(&arg)?),
-			"attack" => Self::Attack(Direction::from_str(&arg)?),
+			"build" => Self::Build(BuildingType::from_str(arg)?),
+			"move" => Self::Move(Coord::from_str(arg)?),
+			"attack" => Self::Attack(Direction::from_str(arg)?),
 			"remove" => Self::Remove,
 			"use" => Self::Use,
 			"claim" => Self::Claim,
-			_ => {return Err(parse_err!("Invalid action '{}'", commtype))}
+			"relocate" => Self::Relocate(Coord::from_str(arg)?),
+			_ => {return Err(ParseError::invalid_word("action", commtype, ACTIONS))}
 		})
 	}
 }
 
+impl fmt::Display for Action {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Build(building) => write!(f, "build {}", building),
+			Self::Move(target) => write!(f, "move {}", target),
+			Self::Attack(dir) => write!(f, "attack {}", dir),
+			Self::Remove => write!(f, "remove"),
+			Self::Use => write!(f, "use"),
+			Self::Claim => write!(f, "claim"),
+			Self::Relocate(target) => write!(f, "relocate {}", target),
+		}
+	}
+}
+
 
 impl FromStr for Command {
 	type Err = ParseError;
 
 	fn from_str(s: &str) -> Result<Self, Self::Err> {
-		let (pos, action) = partition(s);
-		Ok(Self{pos: Pos::from_str(&pos)?, action: Action::from_str(&action)?})
+		let (pos, action) = Coord::partition(s);
+		let pos = Coord::from_str(pos)?;
+		let mut action = Action::from_str(action)?;
+		// In a plot-relative command a plain move target is relative to the same plot
+		if let (Coord::Local{plot, ..}, Action::Move(Coord::Absolute(target))) = (pos, &action) {
+			action = Action::Move(Coord::Local{plot, tile: *target});
+		}
+		Ok(Self{pos, action, condition: None})
+	}
+}
+
+impl fmt::Display for Command {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		if let Some(condition) = &self.condition {
+			write!(f, "if {}: ", condition)?;
+		}
+		write!(f, "{} {}", self.pos, self.action)
+	}
+}
+
+
+pub const MAX_MESSAGE_LENGTH: usize = 500;
+
+/// A line in a command file, which can stand for multiple commands
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Order {
+	Command(Command),
+	/// Do the same action with every matching entity in a plot
+	ForEach{entity: Entity, plot: Pos, action: Action, condition: Option<Condition>},
+	/// "ally <player>": propose an alliance, or accept the proposal of the other player
+	Ally(UserId),
+	/// "leave alliance with <player>"
+	LeaveAlliance(UserId),
+	/// "offer <amount> for <amount> to <player> via <tradepost>"
+	Offer(Trade),
+	/// "accept <amount> for <amount> from <player> via <tradepost>": accept an offer made in the same turn
+	Accept(Trade),
+	/// "say <player>: <text>" or "say everyone: <text>"
+	Say{to: Option<UserId>, text: String}
+}
+
+impl Order {
+	
+	/// The concrete commands for this order in the current state of the field.
+	/// Diplomatic orders don't have any commands
+	pub fn expand(&self, field: &Field) -> Vec<Command> {
+		match self {
+			Self::Ally(_) | Self::LeaveAlliance(_) | Self::Offer(_) | Self::Accept(_) | Self::Say{..} => vec![],
+			Self::Command(command) => vec![command.clone()],
+			Self::ForEach{entity, plot, action, condition} => {
+				let keep = field.keep_location(*plot * field.plot_size);
+				field.find_all(keep, Some(entity.clone()))
+					.into_iter()
+					.map(|pos| Command{pos: pos.into(), action: action.clone(), condition: condition.clone()})
+					.collect()
+			}
+		}
+	}
+	
+	/// Only execute the commands of this order when the condition holds
+	pub fn with_condition(self, new_condition: Condition) -> Result<Self, ParseError> {
+		let combine = |condition: Option<Condition>| Some(match condition {
+			Some(condition) => Condition::And(Box::new(new_condition), Box::new(condition)),
+			None => new_condition
+		});
+		Ok(match self {
+			Self::Command(command) => Self::Command(Command{condition: combine(command.condition), ..command}),
+			Self::ForEach{entity, plot, action, condition} => Self::ForEach{entity, plot, action, condition: combine(condition)},
+			Self::Ally(_) | Self::LeaveAlliance(_) | Self::Offer(_) | Self::Accept(_) | Self::Say{..} => return Err(parse_err!("Diplomatic orders can not have a condition"))
+		})
+	}
+
+	/// Replace the old names of players in diplomatic orders by their current names
+	pub fn resolve_players(&mut self, players: &PlayerRegistry) {
+		let resolve = |user: &mut UserId| if let Some(current) = players.resolve(&user.0) {
+			*user = current.clone();
+		};
+		match self {
+			Self::Ally(other) | Self::LeaveAlliance(other) | Self::Say{to: Some(other), ..} => resolve(other),
+			Self::Offer(trade) | Self::Accept(trade) => resolve(&mut trade.partner),
+			Self::Command(_) | Self::ForEach{..} | Self::Say{to: None, ..} => {}
+		}
+	}
+
+	/// Parse "<player>"
+	fn parse_player(s: &str) -> Result<UserId, ParseError> {
+		let name = s.trim();
+		if name.is_empty() {
+			return Err(parse_err!("Expected a player name").with_word(""));
+		}
+		UserId::validate(name)?;
+		Ok(UserId(name.to_string()))
+	}
+	
+	/// Parse "<entity> in plot <x,y>"
+	fn parse_selection(s: &str) -> Result<(Entity, Pos), ParseError> {
+		let (entity, plot) = partition_by(s, " in plot ");
+		if plot.is_empty() {
+			return Err(parse_err!("Expected '<entity> in plot <x,y>'. Found '{}'", s));
+		}
+		Ok((Entity::from_str(entity.trim())?, Pos::from_str(plot.trim())?))
+	}
+}
+
+impl From<Command> for Order {
+	fn from(command: Command) -> Self {
+		Self::Command(command)
+	}
+}
+
+impl FromStr for Order {
+	type Err = ParseError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		if s.to_lowercase().starts_with("if ") {
+			// if <condition>: <order>
+			// conditions end with a position, so split at the first colon after a digit
+			let split = s.char_indices()
+				.zip(s.chars().skip(1))
+				.find(|((_, c), next)| c.is_ascii_digit() && *next == ':')
+				.map(|((i, _), _)| i + 1)
+				.ok_or_else(|| parse_err!("Expected 'if <condition>: <order>'. Found '{}'", s))?;
+			let condition = Condition::from_str(&s["if ".len()..split])?;
+			return Self::from_str(s[split + 1..].trim())?.with_condition(condition);
+		}
+		let lower = s.to_lowercase();
+		if lower.starts_with("ally ") {
+			return Ok(Self::Ally(Self::parse_player(&s["ally ".len()..])?));
+		}
+		if lower.starts_with("leave alliance with ") {
+			return Ok(Self::LeaveAlliance(Self::parse_player(&s["leave alliance with ".len()..])?));
+		}
+		if lower.starts_with("say ") {
+			let (to, text) = s["say ".len()..].split_once(':').ok_or_else(|| parse_err!("Expected 'say <player>: <message>'. Found '{}'", s))?;
+			let text = text.trim();
+			if text.is_empty() {
+				return Err(parse_err!("The message is empty").with_word(""));
+			}
+			if text.chars().count() > MAX_MESSAGE_LENGTH {
+				return Err(parse_err!("The message is longer than {} characters", MAX_MESSAGE_LENGTH));
+			}
+			// The message ends up in the logs and terminals of other players
+			if let Some(c) = text.chars().find(|c| c.is_control()) {
+				return Err(parse_err!("The message contains invalid character {:?}", c));
+			}
+			let to = if to.trim().eq_ignore_ascii_case("everyone") {None} else {Some(Self::parse_player(to)?)};
+			return Ok(Self::Say{to, text: text.to_string()});
+		}
+		if lower.starts_with("offer ") {
+			return Ok(Self::Offer(Trade::parse(&s["offer ".len()..], "to")?));
+		}
+		if lower.starts_with("accept ") {
+			return Ok(Self::Accept(Trade::parse(&s["accept ".len()..], "from")?));
+		}
+		if s.to_lowercase().starts_with("for each ") {
+			// for each <entity> in plot <x,y>: <action>
+			// entities can contain a colon too, so split at the first colon after the plot
+			let body = &s["for each ".len()..];
+			let split = body.find(" in plot ").and_then(|i| Some(i + body[i..].find(':')?)).unwrap_or(body.len());
+			let (selection, action) = (&body[..split], body[split..].trim_start_matches(':'));
+			let (entity, plot) = Self::parse_selection(selection)?;
+			return Ok(Self::ForEach{entity, plot, action: Action::from_str(action.trim())?, condition: None});
+		}
+		if s.starts_with('@') || s.starts_with(|c: char| c.is_ascii_digit() || c == '-') {
+			return Ok(Self::Command(Command::from_str(s)?));
+		}
+		// <action> all <entity> in plot <x,y>
+		let (action, selection) = partition_by(s, " all ");
+		if selection.is_empty() {
+			return Ok(Self::Command(Command::from_str(s)?));
+		}
+		let (entity, plot) = Self::parse_selection(selection)?;
+		Ok(Self::ForEach{entity, plot, action: Action::from_str(action.trim())?, condition: None})
 	}
 }
 
@@ -62,11 +326,11 @@ mod tests {
 		($command: expr, $out: expr) => {assert_eq!(Command::from_str($command), $out)}
 	}
 	macro_rules! e {
-		($command: expr, $err: expr) => {a!($command, Err(ParseError{msg: $err.to_string()}))}
+		($command: expr, $err: expr) => {assert_eq!(Command::from_str($command).map_err(|e| e.msg), Err($err.to_string()))}
 	}
 	macro_rules! c {
 		($command: expr, ($x: expr, $y: expr), $action: expr) => {
-			a!($command, Ok(Command{pos: Pos::new($x, $y), action: $action}));
+			a!($command, Ok(Command{pos: Pos::new($x, $y).into(), action: $action, condition: None}));
 		}
 	}
 	
@@ -74,7 +338,7 @@ mod tests {
 	fn test_command_parsing() {
 		c!("0,0 build woodcutter", (0, 0), Action::Build(BuildingType::Woodcutter));
 		c!("3,3 build farm", (3, 3), Action::Build(BuildingType::Farm));
-		c!("3,5 move 3,0", (3, 5), Action::Move(Pos::new(3, 0)));
+		c!("3,5 move 3,0", (3, 5), Action::Move(Pos::new(3, 0).into()));
 		c!("-1,6 build farm", (-1, 6), Action::Build(BuildingType::Farm));
 		e!("1,1,1 build farm", "Position must be 2 integers separated by a comma. Found '1,1,1'");
 		e!("invalid build farm", "Position must be 2 integers separated by a comma. Found 'invalid'");
@@ -88,5 +352,79 @@ mod tests {
 		e!("1,1 attack invalid", "Invalid direction 'invalid'");
 		c!("6,6 remove", (6, 6), Action::Remove);
 		c!("7,4 use", (7, 4), Action::Use);
+		c!("5,5 relocate 15,24", (5, 5), Action::Relocate(Pos::new(15, 24).into()));
+	}
+	
+	#[test]
+	fn test_local_coordinates() {
+		let local = |px, py, x, y| Coord::Local{plot: Pos::new(px, py), tile: Pos::new(x, y)};
+		a!("@2,3 7,4 build farm", Ok(Command{pos: local(2, 3, 7, 4), action: Action::Build(BuildingType::Farm), condition: None}));
+		a!("@2,3 7,4 move 3,3", Ok(Command{pos: local(2, 3, 7, 4), action: Action::Move(local(2, 3, 3, 3)), condition: None}));
+		a!("@2,3 7,4 move @2,2 3,9", Ok(Command{pos: local(2, 3, 7, 4), action: Action::Move(local(2, 2, 3, 9)), condition: None}));
+		a!("7,4 move @2,2 3,9", Ok(Command{pos: Pos::new(7, 4).into(), action: Action::Move(local(2, 2, 3, 9)), condition: None}));
+		e!("@2,3 build farm", "Position must be 2 integers separated by a comma. Found 'build'");
+		e!("@2,3", "Position must be 2 integers separated by a comma. Found ''");
+		let size = Pos::new(10, 10);
+		assert_eq!(local(2, 3, 7, 4).resolve(size), Some(Pos::new(27, 34)));
+		assert_eq!(local(2, 3, 10, 4).resolve(size), None);
+		assert_eq!(local(2, 3, 7, -1).resolve(size), None);
+		assert_eq!(Coord::Absolute(Pos::new(-3, 4)).resolve(size), Some(Pos::new(-3, 4)));
+		assert_eq!(local(2, 3, 7, 4).to_string(), "@2,3 7,4");
+		for command in &["@2,3 7,4 move @2,3 3,3", "3,4 attack north", "0,0 build woodcutter", "1,1 claim"] {
+			assert_eq!(Command::from_str(command).unwrap().to_string(), *command);
+		}
+	}
+	
+	#[test]
+	fn test_order_parsing() {
+		let for_each = |entity, x, y, action| Ok(Order::ForEach{entity, plot: Pos::new(x, y), action, condition: None});
+		assert_eq!(Order::from_str("use all woodcutter in plot 1,1"), for_each(Entity::Woodcutter, 1, 1, Action::Use));
+		assert_eq!(Order::from_str("attack north all raider in plot 0,2"), for_each(Entity::Raider, 0, 2, Action::Attack(Direction::North)));
+		assert_eq!(Order::from_str("for each stockpile:wood in plot 3,1: move 9,5"), for_each(Entity::Stockpile(Some(crate::resources::Resource::Wood)), 3, 1, Action::Move(Pos::new(9, 5).into())));
+		assert_eq!(Order::from_str("2,2 use"), Ok(Order::Command(Command{pos: Pos::new(2, 2).into(), action: Action::Use, condition: None})));
+		assert_eq!(Order::from_str("use all woodcutter").map_err(|e| e.msg), Err("Expected '<entity> in plot <x,y>'. Found 'woodcutter'".to_string()));
+		assert_eq!(Order::from_str("use all lumberjack in plot 1,1").map_err(|e| e.msg), Err("Invalid entity 'lumberjack'".to_string()));
+		
+		let condition = |s| Some(Condition::from_str(s).unwrap());
+		assert_eq!(
+			Order::from_str("if stockpile:wood at 3,3: 3,3 move 4,4"),
+			Ok(Order::Command(Command{pos: Pos::new(3, 3).into(), action: Action::Move(Pos::new(4, 4).into()), condition: condition("stockpile:wood at 3,3")}))
+		);
+		assert_eq!(
+			Order::from_str("if owned plot 1,1: if empty 4,4: for each raider in plot 1,1: attack north"),
+			Ok(Order::ForEach{entity: Entity::Raider, plot: Pos::new(1, 1), action: Action::Attack(Direction::North), condition: condition("owned plot 1,1 and empty 4,4")})
+		);
+		assert_eq!(Order::from_str("if empty 4,4 3,3 use").map_err(|e| e.msg), Err("Expected 'if <condition>: <order>'. Found 'if empty 4,4 3,3 use'".to_string()));
+		assert_eq!(Order::from_str("ally bob the builder"), Ok(Order::Ally(UserId("bob the builder".to_string()))));
+		assert_eq!(Order::from_str("Leave alliance with bob"), Ok(Order::LeaveAlliance(UserId("bob".to_string()))));
+		assert_eq!(Order::from_str("ally  ").map_err(|e| e.msg), Err("Expected a player name".to_string()));
+		assert_eq!(Order::from_str("if owned plot 1,1: ally bob").map_err(|e| e.msg), Err("Diplomatic orders can not have a condition".to_string()));
+		assert!(matches!(Order::from_str("offer 3 wood for 2 stone to alice via 19,5"), Ok(Order::Offer(_))));
+		assert!(matches!(Order::from_str("Accept 3 wood for 2 stone from bob via 20,5"), Ok(Order::Accept(_))));
+		assert_eq!(Order::from_str("say alice: let's not fight: ever"), Ok(Order::Say{to: Some(UserId("alice".to_string())), text: "let's not fight: ever".to_string()}));
+		assert_eq!(Order::from_str("say Everyone:hi"), Ok(Order::Say{to: None, text: "hi".to_string()}));
+		assert_eq!(Order::from_str("say alice").map_err(|e| e.msg), Err("Expected 'say <player>: <message>'. Found 'say alice'".to_string()));
+		assert_eq!(Order::from_str("say alice: ").map_err(|e| e.msg), Err("The message is empty".to_string()));
+		assert!(Order::from_str(&format!("say alice: {}", "a".repeat(MAX_MESSAGE_LENGTH + 1))).is_err());
+		assert_eq!(Order::from_str("say everyone: hi\u{1b}[2J").map_err(|e| e.msg), Err("The message contains invalid character '\\u{1b}'".to_string()));
+		assert!(Order::from_str("say alice: one\rtwo").is_err());
+		assert!(Order::from_str("say al\u{7}ice: hi").is_err());
+	}
+	
+	#[test]
+	fn test_resolve_players() {
+		let mut players = PlayerRegistry::from_str("alice; joined:2026-10-01; aliases:al,ally").unwrap();
+		players.register(UserId("bob".to_string()), chrono::NaiveDate::from_ymd_opt(2026, 10, 2).unwrap()).unwrap();
+		let resolved = |s: &str| {
+			let mut order = Order::from_str(s).unwrap();
+			order.resolve_players(&players);
+			order
+		};
+		assert_eq!(resolved("ally al"), Order::Ally(UserId("alice".to_string())));
+		assert_eq!(resolved("leave alliance with ally"), Order::LeaveAlliance(UserId("alice".to_string())));
+		assert_eq!(resolved("say al: hi"), Order::Say{to: Some(UserId("alice".to_string())), text: "hi".to_string()});
+		assert_eq!(resolved("say carol: hi"), Order::Say{to: Some(UserId("carol".to_string())), text: "hi".to_string()});
+		assert!(matches!(resolved("offer 3 wood for 2 stone to al via 19,5"), Order::Offer(trade) if trade.partner.0 == "alice"));
+		assert!(matches!(resolved("accept 3 wood for 2 stone from bob via 19,5"), Order::Accept(trade) if trade.partner.0 == "bob"));
 	}
 }
@@ -0,0 +1,135 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{
+	Pos,
+	UserId,
+	commands::Coord,
+	entity::Entity,
+	field::Field,
+	errors::ParseError,
+	parse_err,
+	utils::partition_by
+};
+
+/// A predicate over the field that decides whether a command is executed.
+/// It is evaluated in the round that the command would be executed.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Condition {
+	Not(Box<Condition>),
+	And(Box<Condition>, Box<Condition>),
+	/// "empty <pos>"
+	Empty(Coord),
+	/// "<entity> at <pos>"
+	Is(Entity, Coord),
+	/// "enemy units in plot <x,y>": the plot is not owned by the player and has units in it
+	EnemyUnits(Pos),
+	/// "owned plot <x,y>": the plot is owned by the player
+	Owned(Pos)
+}
+
+impl Condition {
+
+	pub fn holds(&self, field: &Field, user: &UserId) -> bool {
+		match self {
+			Self::Not(condition) => !condition.holds(field, user),
+			Self::And(a, b) => a.holds(field, user) && b.holds(field, user),
+			Self::Empty(coord) => coord.resolve(field.plot_size).map(|pos| field.get(pos).is_none()).unwrap_or(false),
+			Self::Is(ent, coord) => coord.resolve(field.plot_size).map(|pos| field.get(pos).as_ref() == Some(ent)).unwrap_or(false),
+			Self::EnemyUnits(plot) => {
+				let keep = field.keep_location(*plot * field.plot_size);
+				field.is_valid(keep)
+					&& field.plot_owner(keep).as_ref() != Some(user)
+					&& field.tiles_in_plot(keep).into_iter().filter_map(|p| field.get(p)).any(|ent| ent.properties().defender)
+			}
+			Self::Owned(plot) => {
+				let keep = field.keep_location(*plot * field.plot_size);
+				field.plot_owner(keep).as_ref() == Some(user)
+			}
+		}
+	}
+}
+
+impl FromStr for Condition {
+	type Err = ParseError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let s = s.trim();
+		let lower = s.to_lowercase();
+		let (first, rest) = partition_by(s, " and ");
+		if !rest.is_empty() {
+			return Ok(Self::And(Box::new(Self::from_str(first)?), Box::new(Self::from_str(rest)?)));
+		}
+		if lower.starts_with("not ") {
+			return Ok(Self::Not(Box::new(Self::from_str(&s["not ".len()..])?)));
+		}
+		if lower.starts_with("empty ") {
+			return Ok(Self::Empty(Coord::from_str(s["empty ".len()..].trim())?));
+		}
+		if lower.starts_with("enemy units in plot ") {
+			return Ok(Self::EnemyUnits(Pos::from_str(s["enemy units in plot ".len()..].trim())?));
+		}
+		if lower.starts_with("owned plot ") {
+			return Ok(Self::Owned(Pos::from_str(s["owned plot ".len()..].trim())?));
+		}
+		let (ent, coord) = partition_by(s, " at ");
+		if !coord.is_empty() {
+			return Ok(Self::Is(Entity::from_str(ent.trim())?, Coord::from_str(coord.trim())?));
+		}
+		Err(parse_err!("Invalid condition '{}'", s).with_word(s))
+	}
+}
+
+impl fmt::Display for Condition {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Not(condition) => write!(f, "not {}", condition),
+			Self::And(a, b) => write!(f, "{} and {}", a, b),
+			Self::Empty(coord) => write!(f, "empty {}", coord),
+			Self::Is(ent, coord) => write!(f, "{} at {}", ent, coord),
+			Self::EnemyUnits(plot) => write!(f, "enemy units in plot {}", plot),
+			Self::Owned(plot) => write!(f, "owned plot {}", plot),
+		}
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn user(name: &str) -> UserId {
+		UserId(name.to_string())
+	}
+
+	#[test]
+	fn test_conditions() {
+		let field = Field::from_str(
+			"size:5,5; plot_size:10,10 ;;
+			5,5 keep:user;
+			3,3 farm;
+			15,4 keep:other;
+			12,2 raider;
+			24,5 keep:other;"
+		).unwrap();
+		let holds = |condition: &str| Condition::from_str(condition).unwrap().holds(&field, &user("user"));
+		assert!(holds("empty 4,4"));
+		assert!(!holds("empty 3,3"));
+		assert!(holds("not empty 3,3"));
+		assert!(holds("farm at 3,3"));
+		assert!(holds("farm at @0,0 3,3"));
+		assert!(!holds("farm at @0,0 13,3"));
+		assert!(holds("keep:other at 15,4"));
+		assert!(holds("enemy units in plot 1,0"));
+		assert!(!holds("enemy units in plot 2,0"));
+		assert!(!holds("enemy units in plot 0,0"));
+		assert!(holds("owned plot 0,0"));
+		assert!(!holds("owned plot 1,0"));
+		assert!(holds("empty 4,4 and enemy units in plot 1,0"));
+		assert!(!holds("empty 4,4 and empty 3,3"));
+		for condition in &["empty @1,1 2,2", "not farm at 3,3", "enemy units in plot 2,1", "empty 1,1 and not owned plot 0,0"] {
+			assert_eq!(Condition::from_str(condition).unwrap().to_string(), *condition);
+		}
+		assert_eq!(Condition::from_str("full 3,3").map_err(|e| e.msg), Err("Invalid condition 'full 3,3'".to_string()));
+	}
+}
@@ -0,0 +1,206 @@
+
+use crate::{
+	UserId,
+	errors::{ParseError, CommandError},
+	parse_err,
+	command_err,
+	utils::{percent_encode, percent_decode}
+};
+
+/// The number of turns an alliance still lasts after one of the members leaves it
+pub const LEAVE_COOLDOWN: u64 = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alliance {
+	pub members: (UserId, UserId),
+	/// The turn at which the alliance ends, if one of the members is leaving
+	pub ends: Option<u64>
+}
+
+impl Alliance {
+	fn has(&self, a: &UserId, b: &UserId) -> bool {
+		(&self.members.0 == a && &self.members.1 == b) || (&self.members.0 == b && &self.members.1 == a)
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Declaration {
+	/// The other player still has to declare the alliance too
+	Proposed,
+	Formed,
+	/// The players were already allied
+	Existing
+}
+
+/// Alliances between players, and the alliances that were declared by only one side so far
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Alliances {
+	alliances: Vec<Alliance>,
+	/// (from, to)
+	proposals: Vec<(UserId, UserId)>
+}
+
+impl Alliances {
+
+	pub fn allied(&self, a: &UserId, b: &UserId) -> bool {
+		self.alliances.iter().any(|alliance| alliance.has(a, b))
+	}
+
+	/// Whether the owners of two plots let each other through.
+	/// Unowned plots are only friendly to other unowned plots
+	pub fn friendly(&self, a: &Option<UserId>, b: &Option<UserId>) -> bool {
+		match (a, b) {
+			(Some(a), Some(b)) => a == b || self.allied(a, b),
+			(a, b) => a == b
+		}
+	}
+
+	pub fn allies(&self, user: &UserId) -> Vec<&UserId> {
+		self.alliances.iter().filter_map(|alliance| match &alliance.members {
+			(a, b) if a == user => Some(b),
+			(a, b) if b == user => Some(a),
+			_ => None
+		}).collect()
+	}
+
+	pub fn declare(&mut self, from: &UserId, to: &UserId) -> Result<Declaration, CommandError> {
+		if from == to {
+			return Err(command_err!("You can not ally yourself"));
+		}
+		if let Some(alliance) = self.alliances.iter().find(|alliance| alliance.has(from, to)) {
+			return match alliance.ends {
+				Some(turn) => Err(command_err!("Your alliance with {} is ending at turn {}", to.0, turn)),
+				None => Ok(Declaration::Existing)
+			};
+		}
+		let reverse = (to.clone(), from.clone());
+		if self.proposals.contains(&reverse) {
+			self.proposals.retain(|proposal| proposal != &reverse);
+			self.alliances.push(Alliance{members: (to.clone(), from.clone()), ends: None});
+			return Ok(Declaration::Formed);
+		}
+		let proposal = (from.clone(), to.clone());
+		if !self.proposals.contains(&proposal) {
+			self.proposals.push(proposal);
+		}
+		Ok(Declaration::Proposed)
+	}
+
+	/// Withdraw a proposal, or start leaving an alliance.
+	/// Returns the turn at which the alliance ends, or None when only a proposal was withdrawn
+	pub fn leave(&mut self, from: &UserId, to: &UserId, turn: u64) -> Result<Option<u64>, CommandError> {
+		let proposal = (from.clone(), to.clone());
+		if self.proposals.contains(&proposal) {
+			self.proposals.retain(|p| p != &proposal);
+			return Ok(None);
+		}
+		let alliance = self.alliances.iter_mut()
+			.find(|alliance| alliance.has(from, to))
+			.ok_or_else(|| command_err!("You are not allied with {}", to.0))?;
+		if let Some(ends) = alliance.ends {
+			return Err(command_err!("Your alliance with {} is already ending at turn {}", to.0, ends));
+		}
+		let ends = turn + LEAVE_COOLDOWN;
+		alliance.ends = Some(ends);
+		Ok(Some(ends))
+	}
+
+	/// Remove the alliances that end at this turn, and return their members
+	pub fn expire(&mut self, turn: u64) -> Vec<(UserId, UserId)> {
+		let (ended, remaining) = self.alliances.drain(..).partition(|alliance| alliance.ends.is_some_and(|ends| ends <= turn));
+		self.alliances = remaining;
+		ended.into_iter().map(|alliance: Alliance| alliance.members).collect()
+	}
+
+	pub fn rename(&mut self, old: &UserId, new: &UserId) {
+		let rename = |user: &mut UserId| if user == old {
+			*user = new.clone();
+		};
+		for alliance in self.alliances.iter_mut() {
+			rename(&mut alliance.members.0);
+			rename(&mut alliance.members.1);
+		}
+		for proposal in self.proposals.iter_mut() {
+			rename(&mut proposal.0);
+			rename(&mut proposal.1);
+		}
+	}
+
+	/// Items for the meta section of the world file
+	pub fn to_meta(&self) -> Vec<String> {
+		let mut items: Vec<String> = self.alliances.iter().map(|alliance| {
+			let (a, b) = &alliance.members;
+			match alliance.ends {
+				Some(ends) => format!("alliance:{},{},{}", percent_encode(&a.0), percent_encode(&b.0), ends),
+				None => format!("alliance:{},{}", percent_encode(&a.0), percent_encode(&b.0))
+			}
+		}).collect();
+		items.extend(self.proposals.iter().map(|(from, to)| format!("proposal:{},{}", percent_encode(&from.0), percent_encode(&to.0))));
+		items
+	}
+
+	/// Read an item from the meta section. Returns false if the item is not about alliances
+	pub fn parse_meta(&mut self, name: &str, arg: &str) -> Result<bool, ParseError> {
+		let parts: Vec<&str> = arg.split(',').map(str::trim).collect();
+		let user = |s: &str| UserId(percent_decode(s));
+		match (name, parts.as_slice()) {
+			("alliance", [a, b]) => self.alliances.push(Alliance{members: (user(a), user(b)), ends: None}),
+			("alliance", [a, b, ends]) => {
+				let ends = ends.parse().map_err(|_| parse_err!("Invalid turn '{}'", ends))?;
+				self.alliances.push(Alliance{members: (user(a), user(b)), ends: Some(ends)});
+			}
+			("proposal", [from, to]) => self.proposals.push((user(from), user(to))),
+			("alliance", _) | ("proposal", _) => return Err(parse_err!("Invalid {} '{}'", name, arg)),
+			_ => return Ok(false)
+		}
+		Ok(true)
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn user(name: &str) -> UserId {
+		UserId(name.to_string())
+	}
+
+	#[test]
+	fn test_alliances() {
+		let (alice, bob, carol) = (user("alice"), user("bob"), user("carol, the 2nd"));
+		let mut alliances = Alliances::default();
+		assert!(alliances.declare(&alice, &alice).is_err());
+		assert_eq!(alliances.declare(&alice, &bob), Ok(Declaration::Proposed));
+		assert!(!alliances.allied(&alice, &bob));
+		assert_eq!(alliances.declare(&bob, &alice), Ok(Declaration::Formed));
+		assert!(alliances.allied(&alice, &bob));
+		assert!(alliances.allied(&bob, &alice));
+		assert!(alliances.friendly(&Some(alice.clone()), &Some(bob.clone())));
+		assert!(!alliances.friendly(&Some(alice.clone()), &None));
+		assert_eq!(alliances.declare(&alice, &bob), Ok(Declaration::Existing));
+		assert_eq!(alliances.declare(&carol, &alice), Ok(Declaration::Proposed));
+		assert_eq!(alliances.allies(&alice), vec![&bob]);
+
+		assert_eq!(alliances.leave(&alice, &bob, 5), Ok(Some(5 + LEAVE_COOLDOWN)));
+		assert!(alliances.leave(&bob, &alice, 6).is_err());
+		assert!(alliances.declare(&bob, &alice).is_err());
+		assert!(alliances.leave(&alice, &carol, 6).is_err());
+
+		let mut parsed = Alliances::default();
+		for item in alliances.to_meta() {
+			let (name, arg) = item.split_once(':').unwrap();
+			assert_eq!(parsed.parse_meta(name, arg), Ok(true));
+		}
+		assert_eq!(parsed, alliances);
+		assert_eq!(parsed.parse_meta("turn", "5"), Ok(false));
+		assert!(parsed.parse_meta("alliance", "alice").is_err());
+
+		assert_eq!(alliances.expire(5 + LEAVE_COOLDOWN - 1), vec![]);
+		assert!(alliances.allied(&alice, &bob));
+		assert_eq!(alliances.expire(5 + LEAVE_COOLDOWN), vec![(alice.clone(), bob.clone())]);
+		assert!(!alliances.allied(&alice, &bob));
+		assert_eq!(alliances.leave(&carol, &alice, 9), Ok(None));
+		assert_eq!(alliances.declare(&alice, &carol), Ok(Declaration::Proposed));
+	}
+}
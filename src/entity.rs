@@ -8,13 +8,19 @@ use crate::{
 	resources::Resource,
 	errors::ParseError,
 	parse_err,
+	utils::{percent_encode, percent_decode}
 };
 
+/// The number of resources a warehouse can hold
+pub const WAREHOUSE_CAPACITY: usize = 8;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Entity {
 	
 	Capital(UserId),
 	Keep(UserId),
+	/// The keep of a plot that was abandoned by an inactive player
+	Ruin,
 	
 	Construction(BuildingType),
 	
@@ -32,9 +38,13 @@ pub enum Entity {
 	Barracks,
 	// Special buildings
 	Stockpile(Option<Resource>),
+	/// Holds up to `WAREHOUSE_CAPACITY` resources of any type
+	Warehouse(Vec<Resource>),
 	Road,
 	Tradepost,
 	Scoutpost,
+	/// Only found around the keeps of barbarians
+	Wall,
 	
 	// Ambient
 	Forest,
@@ -66,6 +76,24 @@ macro_rules! props {
 
 impl Entity {
 	
+	/// The resources that are stored on this tile
+	pub fn stored_resources(&self) -> Vec<Resource> {
+		match self {
+			Self::Stockpile(Some(res)) => vec![*res],
+			Self::Warehouse(stored) => stored.clone(),
+			_ => Vec::new()
+		}
+	}
+	
+	/// The number of resources that can still be stored on this tile
+	pub fn storage_room(&self) -> usize {
+		match self {
+			Self::Stockpile(None) => 1,
+			Self::Warehouse(stored) => WAREHOUSE_CAPACITY.saturating_sub(stored.len()),
+			_ => 0
+		}
+	}
+	
 	pub fn properties(&self) -> EntityProperties {
 	
 		let unit = props!(mortal, stopping, movable, defender);
@@ -74,6 +102,7 @@ impl Entity {
 		match self {
 			Self::Capital(_) => props!(destructible, strong, stopping),
 			Self::Keep(_) => props!(destructible, strong, stopping),
+			Self::Ruin => props!(stopping),
 			Self::Raider => unit,
 			Self::Warrior => unit,
 			Self::Ram => props!(removable, destructible, mortal, stopping),
@@ -83,10 +112,12 @@ impl Entity {
 			Self::Lair => building,
 			Self::Barracks => building,
 			Self::Stockpile(_) => props!(removable),
+			Self::Warehouse(_) => building,
 			Self::Construction(_) => small,
 			Self::Road => small,
 			Self::Tradepost => small,
 			Self::Scoutpost => building,
+			Self::Wall => props!(destructible, strong, stopping),
 			Self::Forest => props!(),
 			Self::Swamp => props!(),
 			Self::Rock => props!()
@@ -98,8 +129,10 @@ impl Entity {
 impl fmt::Display for Entity {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		write!(f, "{}", match self {
-			Self::Capital(user) => format!("capital:{}", user.0),
-			Self::Keep(user) => format!("keep:{}", user.0),
+			// Player names are escaped so they can't contain the separators of the world file
+			Self::Capital(user) => format!("capital:{}", percent_encode(&user.0)),
+			Self::Keep(user) => format!("keep:{}", percent_encode(&user.0)),
+			Self::Ruin => "ruin".to_string(),
 			Self::Raider => "raider".to_string(),
 			Self::Warrior => "warrior".to_string(),
 			Self::Ram => "ram".to_string(),
@@ -110,10 +143,13 @@ impl fmt::Display for Entity {
 			Self::Barracks => "barracks".to_string(),
 			Self::Stockpile(Some(res)) => format!("stockpile:{}", res),
 			Self::Stockpile(None) => "stockpile".to_string(),
+			Self::Warehouse(stored) if stored.is_empty() => "warehouse".to_string(),
+			Self::Warehouse(stored) => format!("warehouse:{}", stored.iter().map(Resource::to_string).collect::<Vec<String>>().join(",")),
 			Self::Construction(building) => format!("construction:{}", building),
 			Self::Road => "road".to_string(),
 			Self::Tradepost => "tradepost".to_string(),
 			Self::Scoutpost => "scoutpost".to_string(),
+			Self::Wall => "wall".to_string(),
 			Self::Forest => "forest".to_string(),
 			Self::Swamp => "swamp".to_string(),
 			Self::Rock => "rock".to_string(),
@@ -129,8 +165,9 @@ impl FromStr for Entity {
 		let typ = c.next().unwrap().to_lowercase();
 		let arg = c.next();
 		Ok(match (typ.as_str(), arg) {
-			("capital", Some(user)) => Self::Capital(UserId(user.to_string())),
-			("keep", Some(user)) => Self::Keep(UserId(user.to_string())),
+			("capital", Some(user)) => Self::Capital(UserId(percent_decode(user))),
+			("keep", Some(user)) => Self::Keep(UserId(percent_decode(user))),
+			("ruin", None) => Self::Ruin,
 			("raider", None) => Self::Raider,
 			("warrior", None) => Self::Warrior,
 			("ram", None) => Self::Ram,
@@ -141,14 +178,23 @@ impl FromStr for Entity {
 			("barracks", None) => Self::Barracks,
 			("stockpile", None) => Self::Stockpile(None),
 			("stockpile", Some(res)) => Self::Stockpile(Some(Resource::from_str(res)?)),
+			("warehouse", None) => Self::Warehouse(Vec::new()),
+			("warehouse", Some(stored)) => {
+				let stored = stored.split(',').map(Resource::from_str).collect::<Result<Vec<Resource>, ParseError>>()?;
+				if stored.len() > WAREHOUSE_CAPACITY {
+					return Err(parse_err!("A warehouse can not hold more than {} resources: '{}'", WAREHOUSE_CAPACITY, s));
+				}
+				Self::Warehouse(stored)
+			}
 			("construction", Some(building)) => Self::Construction(BuildingType::from_str(building)?),
 			("road", None) => Self::Road,
 			("tradepost", None) => Self::Tradepost,
 			("scoutpost", None) => Self::Scoutpost,
+			("wall", None) => Self::Wall,
 			("forest", None) => Self::Forest,
 			("swamp", None) => Self::Swamp,
 			("rock", None) => Self::Rock,
-			_ => {return Err(parse_err!("Invalid entity '{}'", s))}
+			_ => {return Err(parse_err!("Invalid entity '{}'", s).with_word(s))}
 		})
 	}
 }
@@ -159,6 +205,7 @@ mod tests {
 	use super::*;
 	use super::Entity::*;
 	use Resource::*;
+	use crate::{Pos, field::Field};
 	
 	fn user(name: &str) -> UserId {
 		UserId(name.to_string())
@@ -190,6 +237,8 @@ mod tests {
 			Stockpile(Some(Food)),
 			Stockpile(Some(Stone)),
 			Stockpile(Some(Iron)),
+			Warehouse(vec![]),
+			Warehouse(vec![Wood, Food, Wood]),
 			Construction(BuildingType::Barracks),
 			Road,
 			Tradepost,
@@ -207,5 +256,40 @@ mod tests {
 			assert_eq!(a, c);
 		}
 	}
+	
+	/// Names with characters that are likely to cause trouble in the world format
+	const NAMES: &[&str] = &["%", "100%", "a;b", "a:b", "a,b", "#1", "bob the builder", "tab\tname", "line\nbreak", "Ælfrida", "😈", "\u{2003}em", "\u{0}", ""];
+	
+	#[test]
+	fn test_escaped_names(){
+		assert_eq!(Keep(user("a;b:c d%")).to_string(), "keep:a%3Bb%3Ac%20d%25");
+		assert_eq!(Keep(user("\u{2003}")).to_string(), "keep:%E2%80%83");
+		assert_eq!(Entity::from_str("keep:%E2%80%83"), Ok(Keep(user("\u{2003}"))));
+		assert_eq!(Entity::from_str("keep:Ælfrida"), Ok(Keep(user("Ælfrida"))));
+		// percent signs that are not escapes written by the encoder are kept, so names from older worlds still read
+		for literal in &["%", "%4", "%zz", "%+1", "%FF", "%41", "%3b", "%C3%86lfrida", "100%"] {
+			assert_eq!(Entity::from_str(&format!("keep:{}", literal)), Ok(Keep(user(literal))), "{}", literal);
+		}
+		for name in NAMES {
+			for ent in &[Keep(user(name)), Capital(user(name))] {
+				let s = ent.to_string();
+				assert!(!s.contains(|c: char| c == ';' || c.is_whitespace()), "{:?}", s);
+				assert_eq!(s.matches(':').count(), 1);
+				assert_eq!(&Entity::from_str(&s).unwrap(), ent);
+			}
+		}
+	}
+	
+	#[test]
+	fn test_field_roundtrip(){
+		let mut field = Field::new(Pos::new(5, 5), Pos::new(20, 10));
+		for (i, keep) in field.list_keeps().into_iter().enumerate() {
+			let name = user(NAMES[i % NAMES.len()]);
+			field.set_tile(keep, if i % 3 == 0 {Capital(name)} else {Keep(name)});
+			field.set_tile(keep + Pos::new(1, 0), Stockpile(Some(Wood)));
+		}
+		let parsed = Field::from_str(&field.to_string()).unwrap();
+		assert_eq!(parsed, field);
+	}
 }
 
@@ -1,24 +1,103 @@
-
 use std::fmt;
+use std::ops::Range;
+
+use crate::utils;
 
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct ParseError {
-	pub msg: String
+	pub msg: String,
+	/// The memory addresses of the word that could not be parsed, when the error is about a single word.
+	/// They are used to find the column of the word in the original line
+	pub word: Option<Range<usize>>,
+	pub suggestion: Option<String>,
+	pub location: Option<SourceLocation>
+}
+
+/// Where in the input an error was found. Lines and columns start at 1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+	pub source: String,
+	pub line: usize,
+	pub column: usize
+}
+
+impl ParseError {
+
+	/// An error for a word that is not one of the candidates, suggesting the closest candidate if there is one
+	pub fn invalid_word<S: AsRef<str>>(kind: &str, word: &str, candidates: &[S]) -> Self {
+		Self {
+			msg: format!("Invalid {} '{}'", kind, word),
+			word: Some(span(word)),
+			suggestion: utils::closest(word, candidates),
+			..Default::default()
+		}
+	}
+
+	/// The error is about `word`, which should be a slice of the parsed text
+	pub fn with_word(mut self, word: &str) -> Self {
+		self.word = Some(span(word));
+		self
+	}
+
+	/// Where the word of the error starts in `text`, if the word was taken from it
+	pub fn word_offset(&self, text: &str) -> Option<usize> {
+		let offset = self.word.as_ref()?.start.checked_sub(text.as_ptr() as usize)?;
+		text.is_char_boundary(offset).then_some(offset)
+	}
+
+	/// Whether the error is about a word that is missing
+	pub fn word_missing(&self) -> bool {
+		self.word.as_ref().is_some_and(Range::is_empty)
+	}
+
+	pub fn at(mut self, location: SourceLocation) -> Self {
+		self.location = Some(location);
+		self
+	}
+}
+
+fn span(word: &str) -> Range<usize> {
+	let start = word.as_ptr() as usize;
+	start..start + word.len()
 }
 
 impl fmt::Display for ParseError {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		write!(f, "ParseError({})", &self.msg)
+		if let Some(location) = &self.location {
+			write!(f, "{}:{}:{}: ", location.source, location.line, location.column)?;
+		}
+		write!(f, "{}", &self.msg)?;
+		if let Some(suggestion) = &self.suggestion {
+			write!(f, " (did you mean '{}'?)", suggestion)?;
+		}
+		Ok(())
 	}
 }
 
 impl std::convert::From<strum::ParseError> for ParseError {
 	fn from(error: strum::ParseError) -> Self {
-		Self{msg: error.to_string()}
+		Self{msg: error.to_string(), ..Default::default()}
 	}
 }
 
 #[macro_export]
 macro_rules! parse_err {
-	($($description:tt)*) => {crate::errors::ParseError{msg: format!($($description)*)}}
+	($($description:tt)*) => {$crate::errors::ParseError{msg: format!($($description)*), ..Default::default()}}
+}
+
+/// Why a command could not be executed
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CommandError {
+	pub msg: String
+}
+
+impl fmt::Display for CommandError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", &self.msg)
+	}
+}
+
+#[macro_export]
+macro_rules! command_err {
+	($($description:tt)*) => {$crate::errors::CommandError{msg: format!($($description)*)}}
 }
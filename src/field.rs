@@ -27,6 +27,10 @@ impl Field {
 		Self {plot_size, size, tiles: HashMap::new()}
 	}
 	
+	pub fn size(&self) -> Size {
+		self.size
+	}
+
 	pub fn get(&self, pos: Pos) -> Option<Entity> {
 		self.tiles.get(&pos).cloned()
 	}
@@ -35,6 +39,7 @@ impl Field {
 		match self.get(self.keep_location(pos)){
 			Some(Entity::Keep(owner)) => Some(owner),
 			Some(Entity::Capital(owner)) => Some(owner),
+			Some(Entity::Ruin) => None,
 			Some(_) => {panic!("plot without keep: {:?}", pos)},
 			None => None
 		}
@@ -132,6 +137,21 @@ impl Field {
 		keeps
 	}
 	
+	/// Give all keeps and capitals of a player to the player with the new name
+	pub fn rename_owner(&mut self, old: &UserId, new: &UserId) -> usize {
+		let mut renamed = 0;
+		for ent in self.tiles.values_mut() {
+			match ent {
+				Entity::Keep(owner) | Entity::Capital(owner) if owner == old => {
+					*owner = new.clone();
+					renamed += 1;
+				}
+				_ => {}
+			}
+		}
+		renamed
+	}
+	
 	pub fn is_valid(&self, pos: Pos) -> bool {
 		let size = self.size * self.plot_size;
 		pos.x >= 0 && pos.y >=0 && pos.x < size.x && pos.y < size.y
@@ -153,15 +173,6 @@ impl Field {
 		}
 	}
 	
-	pub fn cross_pos(&self, to: Pos) -> Option<Pos> {
-		let pos = self.find(self.across_border(to)?, None)?;
-		if self.plot_owner(pos) == self.plot_owner(to) {
-			Some(pos)
-		} else {
-			None
-		}
-	}
-	
 	pub fn neighbours(&self, pos: Pos, val: Option<Entity>) -> bool {
 		for dir in Direction::directions(){
 			let p = pos + dir.to_pos();
@@ -175,29 +186,48 @@ impl Field {
 
 
 
-impl fmt::Display for Field {
-	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		write!(f, "size:{}; plot_size:{};;\n", self.size, self.plot_size)?;
+impl Field {
+	/// Serialise the field with extra items in the meta section, which are ignored when parsing a field
+	pub fn serialise_with_meta(&self, meta: &[String]) -> String {
+		let mut s = format!("size:{}; plot_size:{};", self.size, self.plot_size);
+		for item in meta {
+			s.push_str(&format!(" {};", item));
+		}
+		s.push_str(";\n");
 		for (pos, ent) in self.tiles.iter() {
-			write!(f, "{} {}; ", pos, ent)?;
+			s.push_str(&format!("{} {}; ", pos, ent));
 		}
-		Ok(())
+		s
+	}
+}
+
+impl fmt::Display for Field {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.serialise_with_meta(&[]))
 	}
 }
 
+/// The name and argument of every item in the meta section of a world file
+pub fn parse_meta(s: &str) -> Vec<(String, String)> {
+	let (meta, _tiles) = partition_by(s, ";;");
+	meta.split(';')
+		.map(|item| partition_by(item, ":"))
+		.map(|(name, arg)| (name.trim().to_string(), arg.trim().to_string()))
+		.filter(|(name, _)| !name.is_empty())
+		.collect()
+}
+
 impl FromStr for Field {
 	type Err = ParseError;
 
 	fn from_str(s: &str) -> Result<Self, Self::Err> {
-		let (meta, tiles) = partition_by(s, ";;");
-		let meta_items = meta.split(';');
+		let (_meta, tiles) = partition_by(s, ";;");
 		let mut size = None;
 		let mut plot_size = None;
-		for meta_item in meta_items {
-			let (name, arg) = partition_by(meta_item, ":");
-			match name.trim() {
-				"size" => {size = Some(Pos::from_str(&arg.trim())?)}
-				"plot_size" => {plot_size = Some(Pos::from_str(&arg.trim())?)}
+		for (name, arg) in parse_meta(s) {
+			match name.as_str() {
+				"size" => {size = Some(Pos::from_str(&arg)?)}
+				"plot_size" => {plot_size = Some(Pos::from_str(&arg)?)}
 				_ => {}
 			}
 		}
@@ -206,14 +236,14 @@ impl FromStr for Field {
 				.split(';')
 				.filter_map(|item| {
 					let t = item.trim();
-					if t == "" {
+					if t.is_empty() {
 						return None;
 					}
 					Some(t)
 				})
 				.map(|item| {
 					let (pos_s, ent_s) = partition(item);
-					Ok((Pos::from_str(&pos_s)?, Entity::from_str(&ent_s)?))
+					Ok((Pos::from_str(pos_s)?, Entity::from_str(ent_s)?))
 				})
 				.collect::<Result<HashMap<Pos, Entity>, Self::Err>>()?,
 			size: size.ok_or(parse_err!("No size found for field"))?,
@@ -237,4 +267,15 @@ mod tests {
 			assert!(tiles[i].distance_to(pos) >= tiles[i-1].distance_to(pos));
 		}
 	}
+	
+	#[test]
+	fn test_rename_owner(){
+		let mut field = Field::from_str("size:2,1; plot_size:10,10;; 5,5 capital:alice; 15,4 keep:alice; 3,3 farm;").unwrap();
+		let alice = UserId("alice".to_string());
+		let alicia = UserId("alicia".to_string());
+		assert_eq!(field.rename_owner(&alice, &alicia), 2);
+		assert_eq!(field.get(Pos::new(5, 5)), Some(Entity::Capital(alicia.clone())));
+		assert_eq!(field.plot_owner(Pos::new(12, 2)), Some(alicia));
+		assert_eq!(field.get(Pos::new(3, 3)), Some(Entity::Farm));
+	}
 }
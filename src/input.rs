@@ -1,13 +1,15 @@
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs;
 // use std::env;
 use std::io;
-use std::io::Write;
+use std::io::{Read, Write};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
 use chrono::Utc;
 
 use crate::{
 	UserId,
+	server
 };
 
 pub trait InputMethod {
@@ -16,22 +18,96 @@ pub trait InputMethod {
 	
 	fn find_users(&self) -> Result<Vec<(UserId, Self::IO)>, Self::Err>;
 	fn output(&self, connection: &Self::IO, text: &str) -> Result<(), Self::Err>;
-	fn read_input(&self, connection: &Self::IO) -> Option<String>;
+	/// The name of the source the input was read from, and its text
+	fn read_input(&self, connection: &Self::IO) -> Option<(String, String)>;
+	/// Orders that are executed every turn, in the same form as `read_input`
+	fn read_standing_orders(&self, _connection: &Self::IO) -> Option<(String, String)> {
+		None
+	}
 }
 
+/// The largest command file that is read, in bytes
+const MAX_FILE_SIZE: u64 = 64 * 1024;
+
 #[derive(Debug, Clone)]
 pub struct HomeScraper {
 	pub user_dir: PathBuf,
 	pub game_dir: PathBuf,
 	pub command_fnames: Vec<PathBuf>,
+	pub standing_fname: PathBuf,
 	pub log_fname: PathBuf
 }
 
+/// The game directory of a user, with the home directory and its owner that the files in it must belong to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserDir {
+	pub home: PathBuf,
+	pub game: PathBuf,
+	pub uid: u32
+}
+
+fn rejected(reason: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::PermissionDenied, format!("rejected because {}", reason))
+}
+
+/// Open a file in a directory of a user without following a symlink, so it can not be swapped between checking and opening.
+/// All checks are done on the opened file
+fn open_user_file(options: &mut fs::OpenOptions, path: &Path) -> io::Result<fs::File> {
+	// Opening a fifo for reading would block until someone writes to it
+	let file = options.custom_flags(libc::O_NOFOLLOW | libc::O_NONBLOCK).open(path).map_err(|err| {
+		if err.raw_os_error() == Some(libc::ELOOP) {
+			rejected("it is a symlink")
+		} else {
+			err
+		}
+	})?;
+	let meta = file.metadata()?;
+	if !meta.is_file() {
+		return Err(rejected("it is not a regular file"));
+	}
+	// A hard link could point at a file anywhere else on the same file system
+	if meta.nlink() > 1 {
+		return Err(rejected("it has multiple hard links"));
+	}
+	Ok(file)
+}
+
+/// Read an opened file, unless it is larger than `MAX_FILE_SIZE`
+fn read_limited(file: fs::File) -> io::Result<String> {
+	if file.metadata()?.len() > MAX_FILE_SIZE {
+		return Err(rejected(&format!("it is larger than {} bytes", MAX_FILE_SIZE)));
+	}
+	let mut text = String::new();
+	file.take(MAX_FILE_SIZE).read_to_string(&mut text)?;
+	Ok(text)
+}
+
+impl HomeScraper {
+
+	/// Read a file from the game directory, but only if it really belongs to the user.
+	/// Otherwise anyone with write access to the directory could submit commands for the user.
+	fn read_file(&self, dir: &UserDir, path: &Path) -> io::Result<String> {
+		let file = open_user_file(fs::OpenOptions::new().read(true), path)?;
+		if file.metadata()?.uid() != dir.uid {
+			return Err(rejected("it is not owned by the owner of the home directory"));
+		}
+		read_limited(file)
+	}
+
+	fn log_rejection(&self, dir: &UserDir, path: &Path, err: &io::Error) {
+		if err.kind() == io::ErrorKind::PermissionDenied {
+			// The log of the user might be compromised too, so tell whoever runs the game as well
+			eprintln!("{}  {:?} {}", Utc::now(), path, err);
+		}
+		let _ = self.output(dir, &format!("File error loading {:?}: {}", path, err));
+	}
+}
+
 impl InputMethod for HomeScraper {
 	type Err = io::Error;
-	type IO = PathBuf;
+	type IO = UserDir;
 
-	fn find_users(&self) -> io::Result<Vec<(UserId, PathBuf)>> {
+	fn find_users(&self) -> io::Result<Vec<(UserId, UserDir)>> {
 		Ok(fs::read_dir(&self.user_dir)?
 			.filter_map(|dir_entry| {
 				// Only take the users that have an accessible game directory
@@ -39,29 +115,260 @@ impl InputMethod for HomeScraper {
 				// just ignore this user
 				let dir = dir_entry.ok()?;
 				let name = dir.file_name().into_string().ok()?;
-				let path = dir.path().join(&self.game_dir);
-				let _ = fs::read_dir(&path).ok()?; // filter out users that don't have the directory
-				Some((UserId(name), path))
+				let home = dir.path().canonicalize().ok()?;
+				let uid = fs::metadata(&home).ok()?.uid();
+				let game = home.join(&self.game_dir);
+				let _ = fs::read_dir(&game).ok()?; // filter out users that don't have the directory
+				Some((UserId(name), UserDir{home, game, uid}))
 			}).collect()
 		)
 	}
 	
-	fn read_input(&self, home_dir: &PathBuf) -> Option<String> {
+	fn read_input(&self, dir: &UserDir) -> Option<(String, String)> {
 		for command_fname in self.command_fnames.iter() {
-			let path: PathBuf = home_dir.join(&command_fname);
-			let res = fs::read_to_string(&path).map_err(|err| {
-				let _ = self.output(home_dir, &format!("File error loading {:?}: {}", &path, err.to_string()));
-				err
-			});
+			let path: PathBuf = dir.game.join(command_fname);
+			let res = self.read_file(dir, &path).inspect_err(|err| self.log_rejection(dir, &path, err));
 			if let Ok(command_s) = res {
-				return Some(command_s);
+				return Some((path.to_string_lossy().to_string(), command_s));
 			}
 		}
 		None
 	}
 	
-	fn output(&self, home_dir: &PathBuf, msg: &str) -> io::Result<()> {
-		let mut file = fs::OpenOptions::new().append(true).create(true).open(home_dir.join(&self.log_fname))?;
+	fn read_standing_orders(&self, dir: &UserDir) -> Option<(String, String)> {
+		let path: PathBuf = dir.game.join(&self.standing_fname);
+		let orders = self.read_file(dir, &path).inspect_err(|err| {
+			// Most players have no standing orders so a missing file is not an error
+			if err.kind() != io::ErrorKind::NotFound {
+				self.log_rejection(dir, &path, err);
+			}
+		}).ok()?;
+		Some((path.to_string_lossy().to_string(), orders))
+	}
+	
+	fn output(&self, dir: &UserDir, msg: &str) -> io::Result<()> {
+		let path = dir.game.join(&self.log_fname);
+		let mut file = open_user_file(fs::OpenOptions::new().append(true).create(true), &path)?;
+		writeln!(file, "{}  {}", Utc::now(), msg)
+	}
+}
+
+/// Reads `<player>.txt` files from a single inbox directory, and writes `<player>.log` next to them.
+/// Standing orders are read from `<player>.standing`.
+#[derive(Debug, Clone)]
+pub struct InboxInput {
+	pub dir: PathBuf
+}
+
+impl InboxInput {
+	fn path(&self, user: &UserId, extension: &str) -> PathBuf {
+		self.dir.join(format!("{}.{}", user.0, extension))
+	}
+
+	fn read_file(&self, path: &Path) -> io::Result<String> {
+		read_limited(open_user_file(fs::OpenOptions::new().read(true), path)?)
+	}
+}
+
+impl InputMethod for InboxInput {
+	type Err = io::Error;
+	type IO = UserId;
+
+	fn find_users(&self) -> io::Result<Vec<(UserId, UserId)>> {
+		let mut users: Vec<UserId> = fs::read_dir(&self.dir)?
+			.filter_map(|dir_entry| {
+				let name = dir_entry.ok()?.file_name().into_string().ok()?;
+				let user = name.strip_suffix(".txt").or_else(|| name.strip_suffix(".standing"))?;
+				// The name is used to build the paths of the other files of the player
+				if user.is_empty() || user == "." || user == ".." || user.contains(['/', '\\']) {
+					return None;
+				}
+				Some(UserId(user.to_string()))
+			}).collect();
+		users.sort_by(|a, b| a.0.cmp(&b.0));
+		users.dedup();
+		Ok(users.into_iter().map(|user| (user.clone(), user)).collect())
+	}
+
+	fn read_input(&self, user: &UserId) -> Option<(String, String)> {
+		let path = self.path(user, "txt");
+		let commands = self.read_file(&path).inspect_err(|err| {
+			if err.kind() != io::ErrorKind::NotFound {
+				let _ = self.output(user, &format!("File error loading {:?}: {}", &path, err));
+			}
+		}).ok()?;
+		Some((path.to_string_lossy().to_string(), commands))
+	}
+
+	fn read_standing_orders(&self, user: &UserId) -> Option<(String, String)> {
+		let path = self.path(user, "standing");
+		let orders = self.read_file(&path).inspect_err(|err| {
+			if err.kind() != io::ErrorKind::NotFound {
+				let _ = self.output(user, &format!("File error loading {:?}: {}", &path, err));
+			}
+		}).ok()?;
+		Some((path.to_string_lossy().to_string(), orders))
+	}
+
+	fn output(&self, user: &UserId, msg: &str) -> io::Result<()> {
+		let mut file = open_user_file(fs::OpenOptions::new().append(true).create(true), &self.path(user, "log"))?;
 		writeln!(file, "{}  {}", Utc::now(), msg)
 	}
 }
+
+/// Reads the commands that were submitted to a running command server
+#[derive(Debug, Clone)]
+pub struct SocketInput {
+	pub socket: PathBuf,
+	pub admin_token: String
+}
+
+impl SocketInput {
+	fn request(&self, kind: &str, user: &UserId, body: Option<&str>) -> io::Result<Vec<String>> {
+		server::request(&self.socket, &format!("{} {} {}", kind, self.admin_token, user.0), body)
+	}
+
+	/// Read the lines of a player from the server. Failures are reported in the same way as rejected files
+	fn read_lines(&self, kind: &str, user: &UserId, source: String) -> Option<(String, String)> {
+		let lines = self.request(kind, user, None).inspect_err(|err| {
+			eprintln!("{}  {:?} {}", Utc::now(), source, err);
+			let _ = self.output(user, &format!("Server error loading {:?}: {}", source, err));
+		}).ok()?;
+		if lines.is_empty() {
+			return None;
+		}
+		Some((source, lines.join("\n")))
+	}
+}
+
+impl InputMethod for SocketInput {
+	type Err = io::Error;
+	type IO = UserId;
+
+	fn find_users(&self) -> io::Result<Vec<(UserId, UserId)>> {
+		Ok(server::request(&self.socket, &format!("users {}", self.admin_token), None)?
+			.into_iter()
+			.map(|name| (UserId(name.clone()), UserId(name)))
+			.collect()
+		)
+	}
+
+	fn read_input(&self, user: &UserId) -> Option<(String, String)> {
+		self.read_lines("take", user, format!("{}:{}", self.socket.to_string_lossy(), user.0))
+	}
+
+	fn read_standing_orders(&self, user: &UserId) -> Option<(String, String)> {
+		self.read_lines("standing-orders", user, format!("{}:{}.standing", self.socket.to_string_lossy(), user.0))
+	}
+
+	fn output(&self, user: &UserId, msg: &str) -> io::Result<()> {
+		self.request("output", user, Some(msg)).map(|_| ())
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_inbox() {
+		let dir = std::env::temp_dir().join(format!("evilcadastre-inbox-{}", std::process::id()));
+		let _ = fs::remove_dir_all(&dir);
+		fs::create_dir_all(&dir).unwrap();
+		fs::write(dir.join("alice.txt"), "3,3 claim").unwrap();
+		fs::write(dir.join("bob.standing"), "every turn: 3,3 use").unwrap();
+		fs::write(dir.join("bob.log"), "").unwrap();
+		fs::write(dir.join(".txt"), "").unwrap();
+		fs::write(dir.join("...txt"), "").unwrap();
+		fs::write(dir.join("a\\b.txt"), "").unwrap();
+		let input = InboxInput{dir: dir.clone()};
+		let users = input.find_users().unwrap();
+		let alice = UserId("alice".to_string());
+		let bob = UserId("bob".to_string());
+		assert_eq!(users, vec![(alice.clone(), alice.clone()), (bob.clone(), bob.clone())]);
+		assert_eq!(input.read_input(&alice).map(|(_, text)| text), Some("3,3 claim".to_string()));
+		assert_eq!(input.read_input(&bob), None);
+		assert_eq!(input.read_standing_orders(&bob).map(|(_, text)| text), Some("every turn: 3,3 use".to_string()));
+		input.output(&alice, "hello").unwrap();
+		assert!(fs::read_to_string(dir.join("alice.log")).unwrap().ends_with("  hello\n"));
+
+		let secret = dir.join("secret");
+		fs::write(&secret, "3,3 claim").unwrap();
+		fs::write(dir.join("alice.txt"), "#".repeat(MAX_FILE_SIZE as usize + 1)).unwrap();
+		assert_eq!(input.read_input(&alice), None);
+		fs::remove_file(dir.join("alice.txt")).unwrap();
+		std::os::unix::fs::symlink(&secret, dir.join("alice.txt")).unwrap();
+		assert_eq!(input.read_input(&alice), None);
+		fs::remove_file(dir.join("bob.standing")).unwrap();
+		fs::hard_link(&secret, dir.join("bob.standing")).unwrap();
+		assert_eq!(input.read_standing_orders(&bob), None);
+		let log = fs::read_to_string(dir.join("alice.log")).unwrap();
+		assert!(log.contains("rejected because it is larger than"));
+		assert!(log.contains("rejected because it is a symlink"));
+		assert!(fs::read_to_string(dir.join("bob.log")).unwrap().contains("rejected because it has multiple hard links"));
+		fs::remove_file(dir.join("bob.log")).unwrap();
+		std::os::unix::fs::symlink(&secret, dir.join("bob.log")).unwrap();
+		assert!(input.output(&bob, "hello").is_err());
+		assert_eq!(fs::read_to_string(&secret).unwrap(), "3,3 claim");
+		fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn test_home_scraper() {
+		let base = std::env::temp_dir().join(format!("evilcadastre-homes-{}", std::process::id()));
+		let _ = fs::remove_dir_all(&base);
+		let game = base.join("alice/.cadastre/evil");
+		fs::create_dir_all(&game).unwrap();
+		fs::write(base.join("secret"), "3,3 claim").unwrap();
+		let input = HomeScraper {
+			user_dir: base.clone(),
+			game_dir: PathBuf::from(".cadastre/evil"),
+			command_fnames: vec![PathBuf::from("world")],
+			standing_fname: PathBuf::from("world.standing"),
+			log_fname: PathBuf::from("world.log")
+		};
+		let users = input.find_users().unwrap();
+		assert_eq!(users.len(), 1);
+		let (user, dir) = &users[0];
+		assert_eq!(user, &UserId("alice".to_string()));
+		let log = || fs::read_to_string(game.join("world.log")).unwrap_or_default();
+
+		fs::write(game.join("world"), "3,3 claim").unwrap();
+		assert_eq!(input.read_input(dir).map(|(_, text)| text), Some("3,3 claim".to_string()));
+
+		fs::write(game.join("world"), "#".repeat(MAX_FILE_SIZE as usize + 1)).unwrap();
+		assert_eq!(input.read_input(dir), None);
+		assert!(log().contains("rejected because it is larger than"));
+
+		fs::remove_file(game.join("world")).unwrap();
+		std::os::unix::fs::symlink(base.join("secret"), game.join("world")).unwrap();
+		assert_eq!(input.read_input(dir), None);
+		assert!(log().contains("rejected because it is a symlink"));
+
+		fs::remove_file(game.join("world")).unwrap();
+		fs::hard_link(base.join("secret"), game.join("world")).unwrap();
+		assert_eq!(input.read_input(dir), None);
+		assert!(log().contains("rejected because it has multiple hard links"));
+
+		std::os::unix::fs::symlink(base.join("secret"), game.join("world.standing")).unwrap();
+		assert_eq!(input.read_standing_orders(dir), None);
+
+		fs::remove_file(game.join("world")).unwrap();
+		fs::write(game.join("world"), "3,3 claim").unwrap();
+		// Changing the owner only works when running as root
+		if std::os::unix::fs::chown(game.join("world"), Some(dir.uid + 1), None).is_ok() {
+			assert_eq!(input.read_input(dir), None);
+			assert!(log().contains("rejected because it is not owned by the owner of the home directory"));
+		}
+
+		fs::remove_file(game.join("world.log")).unwrap();
+		std::os::unix::fs::symlink(base.join("secret"), game.join("world.log")).unwrap();
+		assert!(input.output(dir, "hello").is_err());
+		fs::remove_file(game.join("world.log")).unwrap();
+		fs::hard_link(base.join("secret"), game.join("world.log")).unwrap();
+		assert!(input.output(dir, "hello").is_err());
+		assert_eq!(fs::read_to_string(base.join("secret")).unwrap(), "3,3 claim");
+		fs::remove_dir_all(&base).unwrap();
+	}
+}
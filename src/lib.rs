@@ -3,6 +3,7 @@
 pub mod input;
 pub mod locations;
 pub mod commands;
+pub mod conditions;
 pub mod user;
 mod utils;
 mod errors;
@@ -13,6 +14,18 @@ pub mod world;
 mod buildings;
 mod resources;
 mod rules;
+pub mod render;
+pub mod report;
+pub mod server;
+pub mod diplomacy;
+pub mod trade;
+pub mod scoring;
+pub mod season;
+pub mod activity;
+pub mod respawn;
+pub mod barbarians;
+pub mod bot;
+pub mod simulation;
 
 use crate::{
 	locations::{Pos, Size},
@@ -85,10 +85,10 @@ impl FromStr for Pos {
 	fn from_str(s: &str) -> Result<Self, Self::Err> {
 		let coords: Vec<&str> = s.split(',').collect();
 		if coords.len() != 2 {
-			return Err(parse_err!("Position must be 2 integers separated by a comma. Found '{}'", s));
+			return Err(parse_err!("Position must be 2 integers separated by a comma. Found '{}'", s).with_word(s));
 		}
-		let x_fromstr = coords[0].parse::<i32>().map_err(|e|parse_err!("Invalid Position '{}': {}", s, e.to_string()))?;
-		let y_fromstr = coords[1].parse::<i32>().map_err(|e|parse_err!("Invalid Position '{}': {}", s, e.to_string()))?;
+		let x_fromstr = coords[0].parse::<i32>().map_err(|e|parse_err!("Invalid Position '{}': {}", s, e).with_word(s))?;
+		let y_fromstr = coords[1].parse::<i32>().map_err(|e|parse_err!("Invalid Position '{}': {}", s, e).with_word(s))?;
 
 		Ok(Self::new(x_fromstr, y_fromstr))
 	}
@@ -146,7 +146,7 @@ impl FromStr for Direction {
 			"south" => Ok(Self::South),
 			"east" => Ok(Self::East),
 			"west" => Ok(Self::West),
-			_ => Err(parse_err!("Invalid direction '{}'", s))
+			_ => Err(ParseError::invalid_word("direction", s, &Self::directions().iter().map(Self::to_string).collect::<Vec<String>>()))
 		}
 	}
 }
@@ -1,19 +1,33 @@
 
 
 use std::path::PathBuf;
-use std::io::Read;
+use std::io::{Read, Write};
 use std::str::FromStr;
+use std::fmt::{Debug, Display};
+use std::fs;
+use std::os::unix::net::UnixListener;
 
 use structopt::StructOpt;
+use chrono::Utc;
 
 use evilcadastre::{
-	input::{InputMethod, HomeScraper},
-	user::UserId,
-	commands::Command,
+	input::{InputMethod, HomeScraper, InboxInput, SocketInput},
+	user::{UserId, PlayerRegistry, Player},
+	commands::Order,
 	parser,
 	field::Field,
 	world::World,
-	locations::Pos
+	locations::Pos,
+	report::Report,
+	render,
+	server::{self, Server},
+	scoring::{self, VictoryCondition},
+	season::{self, Phase},
+	respawn::RespawnPolicy,
+	barbarians,
+	bot::{self, Bot, Strategy},
+	trade::{self, Amount},
+	simulation
 };
 
 
@@ -21,7 +35,15 @@ use evilcadastre::{
 #[structopt(name = "Evil Cadastre", about = "A turn-based stragegy game")]
 enum Arguments {
 	Init(InitArgs),
-	Update(UpdateArgs)
+	Update(UpdateArgs),
+	Render(RenderArgs),
+	Serve(ServeArgs),
+	Submit(SubmitArgs),
+	Player(PlayerArgs),
+	Season(SeasonArgs),
+	Simulate(SimulateArgs),
+	#[structopt(about = "Print the leaderboard of the world read from stdin")]
+	Scores
 }
 
 #[derive(StructOpt)]
@@ -32,7 +54,42 @@ pub struct InitArgs {
 	plot_size: Pos,
 	
 	#[structopt(short="s", long, help="the width and height of the world, measured in number of plots")]
-	world_size: Pos
+	world_size: Pos,
+	
+	#[structopt(long, help="A way to win the game: 'plots <percentage>', 'last capital' or 'score after <turns>'. Can be given multiple times")]
+	victory: Vec<VictoryCondition>,
+	
+	#[structopt(long, help="The number of turns without commands after which the plots of a player fall into ruin")]
+	decay_after: Option<u64>,
+	
+	#[structopt(long, help="What happens to players that lost their capital, for example 'cooldown 5, spawn, 5 wood, 5 food'")]
+	respawn: Option<RespawnPolicy>,
+	
+	#[structopt(long, help="The food every unit eats each turn. Units that can not be fed desert")]
+	upkeep: Option<usize>,
+	
+	#[structopt(long, default_value="0", help="The number of plots that are given to the barbarians")]
+	barbarians: usize,
+	
+	#[structopt(long, default_value="0", help="The seed for the forests, swamps and barbarian plots. The same seed gives the same world")]
+	seed: u32,
+	
+	#[structopt(long, use_delimiter = true, help="The cost of taking over a plot with a scoutpost, for example '10 wood, 5 food, 5 stone'")]
+	takeover_cost: Vec<Amount>
+}
+
+#[derive(StructOpt)]
+#[structopt(about = "Let bots play in a new world and print statistics for every turn as CSV")]
+pub struct SimulateArgs {
+
+	#[structopt(flatten)]
+	world: InitArgs,
+	
+	#[structopt(short, long, default_value="100", help="The number of turns to play")]
+	turns: u64,
+	
+	#[structopt(long, help="A bot that plays, written as '<player>:<strategy>'. Can be given multiple times. Defaults to one bot of every strategy")]
+	bot: Vec<Bot>
 }
 
 #[derive(StructOpt)]
@@ -45,267 +102,455 @@ pub struct UpdateArgs {
 	#[structopt(short, long, default_value=".cadastre/evil/", help="The path to the directory where the game files are relative to the home directory")]
 	game_dir: String,
 	
-	#[structopt(short, long, required(true), help="The name that identifies the commands for this world")]
-	world_name: Vec<String>
+	#[structopt(short, long, required_unless_one=&["socket", "inbox"], help="The name that identifies the commands for this world")]
+	world_name: Vec<String>,
+
+	#[structopt(long, requires="admin-token-file", help="Read the commands from the command server listening on this socket instead of from the user directories")]
+	socket: Option<PathBuf>,
+
+	#[structopt(long, conflicts_with="socket", help="Read the commands from <player>.txt files in this directory instead of from the user directories")]
+	inbox: Option<PathBuf>,
+
+	#[structopt(long, requires="socket", help="The file with the admin token of the command server")]
+	admin_token_file: Option<PathBuf>,
+
+	#[structopt(long, help="The player registry, used to find players by their old names")]
+	registry: Option<PathBuf>,
+
+	#[structopt(long, help="Write the leaderboard after the update to this file")]
+	leaderboard: Option<PathBuf>,
+
+	#[structopt(long, help="Let a bot play, written as '<player>:<strategy>' where the strategy is economic, turtle or raider. Can be given multiple times")]
+	bot: Vec<Bot>
+
+}
+
+#[derive(StructOpt)]
+#[structopt(about = "Draw the world read from stdin as a standalone HTML page or SVG image")]
+pub struct RenderArgs {
+
+	#[structopt(short, long, default_value="html", help="The output format: html or svg")]
+	format: render::Format,
+	
+	#[structopt(long, conflicts_with="format", help="Write a PNG minimap instead, with one block of pixels per tile")]
+	png: bool,
 	
+	#[structopt(long, default_value="4", help="The width and height in pixels of a tile on the PNG minimap")]
+	scale: u32,
+	
+	#[structopt(long, help="The player registry with the display names and colours of the players")]
+	registry: Option<PathBuf>
+}
+
+#[derive(StructOpt)]
+#[structopt(about = "Run a server that collects commands from players over a local socket until the next update")]
+pub struct ServeArgs {
+
+	#[structopt(long, help="The path of the socket to listen on")]
+	socket: PathBuf,
+	
+	#[structopt(long, help="The file with a line '<token> <user>' for every player")]
+	tokens_file: PathBuf,
+	
+	#[structopt(long, help="The file with the token that the update uses to read the commands")]
+	admin_token_file: PathBuf
+}
+
+#[derive(StructOpt)]
+#[structopt(about = "Send the commands read from stdin to a command server, or fetch the feedback")]
+pub struct SubmitArgs {
+
+	#[structopt(long, help="The path of the socket of the command server")]
+	socket: PathBuf,
+	
+	#[structopt(long, help="The file with the token of the player")]
+	token_file: PathBuf,
+	
+	#[structopt(long, help="Submit standing orders instead of the commands for the next turn")]
+	standing: bool,
+	
+	#[structopt(long, conflicts_with="standing", help="Print the feedback of the previous turns instead of submitting commands")]
+	feedback: bool
+}
+
+#[derive(StructOpt)]
+#[structopt(about = "Manage the registered players")]
+pub struct PlayerArgs {
+
+	#[structopt(long, help="The player registry file")]
+	registry: PathBuf,
+	
+	#[structopt(subcommand)]
+	command: PlayerCommand
+}
+
+#[derive(StructOpt)]
+pub enum PlayerCommand {
+	#[structopt(about = "Add a new player")]
+	Register {
+		name: String,
+		#[structopt(long)]
+		display_name: Option<String>,
+		#[structopt(long, help="A colour in the form #rrggbb")]
+		colour: Option<render::Colour>,
+		#[structopt(long, help="Other names that refer to this player, separated by commas")]
+		aliases: Option<String>
+	},
+	#[structopt(about = "Give a player a new name, and rewrite the keeps in the world read from stdin")]
+	Rename {
+		old: String,
+		new: String
+	},
+	#[structopt(about = "List all players")]
+	List
+}
+
+#[derive(StructOpt)]
+#[structopt(about = "Run a season: registration, play, pauses, the end and the archive")]
+pub struct SeasonArgs {
+
+	#[structopt(long, help="The world file of the season")]
+	world: PathBuf,
+	
+	#[structopt(subcommand)]
+	command: SeasonCommand
+}
+
+#[derive(StructOpt)]
+pub enum SeasonCommand {
+	#[structopt(about = "Create the world of a new season, in which players can only claim their first plot")]
+	Open(InitArgs),
+	#[structopt(about = "Start play after the registration, or continue after a pause")]
+	Start,
+	#[structopt(about = "Stop executing commands until the season is started again")]
+	Pause,
+	#[structopt(about = "End the season and write the final standings")]
+	End {
+		#[structopt(long, help="Write the standings to this file instead of stdout")]
+		standings: Option<PathBuf>
+	},
+	#[structopt(about = "Move the world and the other files of an ended season to a directory")]
+	Archive {
+		#[structopt(help="The directory to archive the season in")]
+		to: PathBuf,
+		#[structopt(help="Other files of the season, like the leaderboard and the standings")]
+		files: Vec<PathBuf>
+	}
 }
 
 pub fn main(){
 
 	match Arguments::from_args() {
 		Arguments::Init(init_args) => init(init_args),
-		Arguments::Update(update_args) => update(update_args)
+		Arguments::Update(update_args) => update(update_args),
+		Arguments::Render(render_args) => render(render_args),
+		Arguments::Serve(serve_args) => serve(serve_args),
+		Arguments::Submit(submit_args) => submit(submit_args),
+		Arguments::Player(player_args) => player(player_args),
+		Arguments::Season(season_args) => season(season_args),
+		Arguments::Simulate(simulate_args) => simulate(simulate_args),
+		Arguments::Scores => scores()
 	}
 }
 
 pub fn init(args: InitArgs){
-	let world = World::init(args.plot_size, args.world_size);
-	println!("{}", world.serialise());
+	println!("{}", new_world(args).serialise());
 }
 
-pub fn update(args: UpdateArgs){
-	let input = HomeScraper {
-		user_dir: PathBuf::from(args.home_dirs),
-		game_dir: PathBuf::from(args.game_dir),
-		command_fnames: args.world_name.iter().map(PathBuf::from).collect(),
-		log_fname: PathBuf::from(format!("{}.log", args.world_name[0]))
+fn new_world(args: InitArgs) -> World {
+	let mut world = World::init(args.plot_size, args.world_size, args.seed);
+	barbarians::seed(&mut world.field, args.barbarians, args.seed);
+	world.victory = args.victory;
+	world.decay_after = args.decay_after;
+	world.respawn = args.respawn;
+	world.upkeep = args.upkeep;
+	if !args.takeover_cost.is_empty() {
+		world.takeover_cost = args.takeover_cost;
+	}
+	world
+}
+
+pub fn simulate(args: SimulateArgs){
+	let bots = if args.bot.is_empty() {
+		[Strategy::Economic, Strategy::Turtle, Strategy::Raider].iter()
+			.map(|strategy| Bot{user: UserId(strategy.to_string()), strategy: *strategy})
+			.collect()
+	} else {
+		args.bot
 	};
-	let all_commands = read_all_commands(&input);
+	let mut world = new_world(args.world);
+	println!("{}", simulation::CSV_HEADER);
+	for row in simulation::simulate(&mut world, &bots, args.turns) {
+		println!("{}", row);
+	}
+}
+
+pub fn update(args: UpdateArgs){
+	let players = read_registry(args.registry.as_ref());
+	if let Some(socket) = args.socket {
+		let admin_token = read_token(&args.admin_token_file.expect("--socket requires --admin-token-file"));
+		if admin_token.is_empty() {
+			eprintln!("Error: The admin token can not be empty");
+			std::process::exit(1);
+		}
+		update_with(&SocketInput {socket, admin_token}, &players, args.leaderboard.as_ref(), &args.bot);
+	} else if let Some(dir) = args.inbox {
+		update_with(&InboxInput {dir}, &players, args.leaderboard.as_ref(), &args.bot);
+	} else {
+		update_with(&HomeScraper {
+			user_dir: PathBuf::from(args.home_dirs),
+			game_dir: PathBuf::from(args.game_dir),
+			command_fnames: args.world_name.iter().map(PathBuf::from).collect(),
+			standing_fname: PathBuf::from(format!("{}.standing", args.world_name[0])),
+			log_fname: PathBuf::from(format!("{}.log", args.world_name[0]))
+		}, &players, args.leaderboard.as_ref(), &args.bot);
+	}
+}
+
+fn update_with<I: InputMethod>(input: &I, players: &PlayerRegistry, leaderboard: Option<&PathBuf>, bots: &[Bot]) where I::Err: Debug {
+	let users: Vec<(UserId, I::IO)> = input.find_users().expect("Can not find user list")
+		.into_iter()
+		.map(|(userid, connection)| (players.resolve(&userid.0).cloned().unwrap_or(userid), connection))
+		.filter(|(userid, _)| {
+			UserId::validate(&userid.0).inspect_err(|err| eprintln!("Ignoring player: {}", err)).is_ok()
+		})
+		.collect();
+	let mut all_commands = read_all_commands(input, &users);
+	let mut standing_orders = read_all_standing_orders(input, &users);
+	for (_, orders) in all_commands.iter_mut().chain(standing_orders.iter_mut()) {
+		for order in orders.iter_mut() {
+			order.resolve_players(players);
+		}
+	}
 	let mut world_s = String::new();
 	std::io::stdin().read_to_string(&mut world_s).unwrap();
 // 	let world_s = fs::read_to_string("world.evil").expect("failed to load world");
-	let mut world = World::new(Field::from_str(&world_s).expect("Invalid world"));
-	world.update(&all_commands);
+	let mut world = World::from_str(&world_s).expect("Invalid world");
+	for bot in bots {
+		let commands = bot::commands(&world.field, &bot.user, bot.strategy, &trade::total(&world.takeover_cost));
+		all_commands.push((bot.user.clone(), commands.into_iter().map(Order::Command).collect()));
+	}
+	let report = world.update_with_standing_orders(&all_commands, &standing_orders);
+	write_report(input, &users, &report);
+	if let Some(path) = leaderboard {
+		fs::write(path, scoring::leaderboard(&world.field, world.turn)).expect("Can not write leaderboard");
+	}
 	println!("{}", world.serialise());
 }
 
 
-
-pub fn read_all_commands(input: &HomeScraper) -> Vec<(UserId, Vec<Command>)>{
-	let users = input.find_users().expect("Can not find user list");
-	users.into_iter().filter_map(|(userid, connection)| {
-		let command_text = input.read_input(&connection)?;
-		let user_commands: Vec<Command> = parser::parse_input(&command_text).into_iter().filter_map(|res| 
-			res.map_err(|pe| {
-				let _ = input.output(&connection, &format!("Parse Error: {:?}", pe));
-				pe
-			}).ok()
-		).collect();
-		Some((userid, user_commands))
-	}).collect()
+pub fn render(args: RenderArgs){
+	let mut world_s = String::new();
+	std::io::stdin().read_to_string(&mut world_s).unwrap();
+	let field = Field::from_str(&world_s).expect("Invalid world");
+	let players = read_registry(args.registry.as_ref());
+	if args.png {
+		let image = render::render_png(&field, &players, args.scale).expect("Failed to encode PNG");
+		std::io::stdout().write_all(&image).expect("Failed to write PNG");
+	} else {
+		println!("{}", render::render(&field, &players, args.format));
+	}
 }
 
-
-
-#[cfg(test)]
-mod tests {
-	use super::*;
-	use std::str::FromStr;
-	use crate::entity::Entity;
-	
-	macro_rules! tileis {
-			($world: expr, $x: expr, $y: expr, $val: expr) => {assert_eq!($world.field.get(Pos::new($x, $y)), $val)}
-	}
-	
-	fn parse_commands(u: &str, c: &[&str]) -> (UserId, Vec<Command>) {
-		(UserId(u.to_string()), c.iter().map(|s| Command::from_str(s).unwrap()).collect())
+pub fn scores(){
+	let mut world_s = String::new();
+	std::io::stdin().read_to_string(&mut world_s).unwrap();
+	let world = World::from_str(&world_s).expect("Invalid world");
+	print!("{}", scoring::leaderboard(&world.field, world.turn));
+	if let Some((winner, turn)) = &world.winner {
+		println!("{} won the game at turn {}", winner.0, turn);
 	}
-	
-	#[test]
-	fn test_simple_commands() {
-		let mut world = World {field: Field::from_str("size:5,5; plot_size:10,10 ;;").unwrap()};
-		let (user, commands) = parse_commands("user", &[
-			"2,1 build stockpile",
-			"15,2 build woodcutter",
-			"6,2 build woodcutter",
-			"6,3 build woodcutter",
-			"0,0 claim",
-			"11,1 claim",
-			"11,2 build stockpile",
-			"6,2 build stockpile",
-			"8,0 build stockpile",
-			"8,1 build stockpile",
-			"8,2 build stockpile",
-			"8,3 build stockpile",
-			"8,4 build stockpile",
-			"8,5 build stockpile"
-		]);
-		world.update(&vec![(user.clone(), commands)]);
-		assert_eq!(world.field.plot_owner(Pos::new(0,0)), Some(user.clone()));
-		assert_eq!(world.field.plot_owner(Pos::new(9,9)), Some(user.clone()));
-		assert_eq!(world.field.plot_owner(Pos::new(11,11)), None);
-		assert_eq!(world.field.plot_owner(Pos::new(1,11)), None);
-		assert_eq!(world.field.plot_owner(Pos::new(11,1)), None);
-		tileis!(world, 2,1, None);//Some(Entity::Stockpile(None)));
-		tileis!(world, 15,2, None);
-		tileis!(world, 6,2, Some(Entity::Stockpile(None)));
-		tileis!(world, 6,3, None);
-		tileis!(world, 11,2, None);
-		tileis!(world, 8,0, Some(Entity::Stockpile(None)));
-		tileis!(world, 8,1, Some(Entity::Stockpile(None)));
-		tileis!(world, 8,2, None);
-		assert_eq!(world.field, Field::from_str(
-			"size:5,5; plot_size:10,10 ;;
-			5,5 keep:user;
-			6,2 stockpile;
-			8,0 stockpile;
-			8,1 stockpile;"
-		).unwrap());
+}
+
+pub fn player(args: PlayerArgs){
+	let mut players = if args.registry.exists() {
+		read_registry(Some(&args.registry))
+	} else {
+		PlayerRegistry::default()
+	};
+	let result = match args.command {
+		PlayerCommand::Register{name, display_name, colour, aliases} => {
+			let aliases: Vec<UserId> = aliases.unwrap_or_default()
+				.split(',')
+				.map(str::trim)
+				.filter(|alias| !alias.is_empty())
+				.map(|alias| UserId(alias.to_string()))
+				.collect();
+			let id = UserId(name);
+			display_name.as_deref().map(Player::validate_display_name).transpose()
+				.and_then(|_| players.register(id.clone(), Utc::today().naive_utc()))
+				.map(|player| {
+					player.display_name = display_name;
+					player.colour = colour;
+				})
+				.and_then(|_| aliases.into_iter().try_for_each(|alias| players.add_alias(&id, alias)))
+		}
+		PlayerCommand::Rename{old, new} => {
+			let (old, new) = (UserId(old), UserId(new));
+			players.rename(&old, new.clone()).map(|_| {
+				let mut world_s = String::new();
+				std::io::stdin().read_to_string(&mut world_s).unwrap();
+				let mut world = World::from_str(&world_s).expect("Invalid world");
+				world.rename_player(&old, &new);
+				println!("{}", world.serialise());
+			})
+		}
+		PlayerCommand::List => {
+			print!("{}", players);
+			return;
+		}
+	};
+	if let Err(err) = result {
+		eprintln!("Error: {}", err);
+		std::process::exit(1);
 	}
-	
-	#[test]
-	fn test_woodcutting(){
-		let mut world = World {field: Field::from_str(
-			"size:5,5; plot_size:10,10 ;;
-			5,5 keep:user;
-			0,5 woodcutter;
-			1,5 stockpile;
-			2,5 stockpile;
-			0,2 stockpile;
-			9,5 woodcutter;
-			10,5 stockpile;"
-		).unwrap()};
-		let (user, commands) = parse_commands("user", &[
-			"0,5 use",
-			"9,5 use"
-		]);
-		world.update(&vec![(user, commands)]);
-		
-		assert_eq!(world.field, Field::from_str(
-			"size:5,5; plot_size:10,10 ;;
-			5,5 keep:user;
-			0,5 woodcutter;
-			1,5 stockpile:wood;
-			2,5 stockpile:wood;
-			0,2 stockpile;
-			9,5 woodcutter;
-			10,5 stockpile;"
-		).unwrap());
+	fs::write(&args.registry, players.to_string()).expect("Can not write player registry");
+}
+
+pub fn season(args: SeasonArgs){
+	let result = match args.command {
+		SeasonCommand::Open(init_args) => {
+			if args.world.exists() {
+				eprintln!("Error: {} already exists. Archive the previous season first", args.world.display());
+				std::process::exit(1);
+			}
+			let mut world = new_world(init_args);
+			world.phase = Phase::Registration;
+			Ok(world)
+		}
+		SeasonCommand::Start => {
+			let mut world = read_world(&args.world);
+			season::start(&mut world).map(|_| world)
+		}
+		SeasonCommand::Pause => {
+			let mut world = read_world(&args.world);
+			season::pause(&mut world).map(|_| world)
+		}
+		SeasonCommand::End{standings} => {
+			let mut world = read_world(&args.world);
+			let already_ended = world.phase == Phase::Ended;
+			let text = season::end(&mut world);
+			match standings {
+				Some(path) => fs::write(path, text).expect("Can not write standings"),
+				None => print!("{}", text)
+			}
+			if already_ended {
+				return;
+			}
+			Ok(world)
+		}
+		SeasonCommand::Archive{to, files} => {
+			if !args.world.exists() {
+				eprintln!("Error: {} does not exist. This season may have been archived already", args.world.display());
+				std::process::exit(1);
+			}
+			let world = read_world(&args.world);
+			if world.phase != Phase::Ended {
+				eprintln!("Error: Only an ended season can be archived. This season is {}", world.phase);
+				std::process::exit(1);
+			}
+			let sources: Vec<&PathBuf> = files.iter().chain(std::iter::once(&args.world)).collect();
+			let targets: Vec<PathBuf> = sources.iter()
+				.map(|file| to.join(file.file_name().expect("Invalid file name")))
+				.collect();
+			// Archiving must not overwrite an earlier season
+			if let Some(existing) = targets.iter().find(|target| target.exists()) {
+				eprintln!("Error: {} already exists. This season may have been archived already", existing.display());
+				std::process::exit(1);
+			}
+			fs::create_dir_all(&to).expect("Can not create archive directory");
+			for (file, target) in sources.iter().zip(targets) {
+				fs::rename(file, target).expect("Can not move file to archive");
+			}
+			return;
+		}
+	};
+	match result {
+		Ok(world) => fs::write(&args.world, format!("{}\n", world.serialise())).expect("Can not write world"),
+		Err(err) => {
+			eprintln!("Error: {}", err);
+			std::process::exit(1);
+		}
 	}
-	
-	#[test]
-	fn test_attack(){
-		let mut world = World {field: Field::from_str(
-			"size:5,5; plot_size:10,10 ;;
-			5,5 keep:user;
-			6,6 lair;
-			1,9 raider;
-			3,3 woodcutter;
-			3,7 raider;
-			
-			15,4 keep:user;
-			11,6 raider;
-			
-			4,15 keep:other;
-			1,13 farm;
-			3,17 raider;
-			3,16 farm;"
-		).unwrap()};
-		world.update(&vec![
-			parse_commands("user", &[
-				"1,9 attack south",
-				"11,6 attack west"
-			]),
-			parse_commands("other", &[
-				"3,17 attack north",
-			])
-		]);
-		
-		assert_eq!(world.field, Field::from_str(
-			"size:5,5; plot_size:10,10 ;;
-			5,5 keep:user;
-			6,6 lair;
-			1,9 raider;
-			3,3 woodcutter;
-			3,7 raider;
-			
-			15,4 keep:user;
-			11,6 raider;
-			
-			4,15 keep:other;
-			3,17 raider;
-			3,16 farm;"
-		).unwrap());
+}
+
+fn read_world(path: &PathBuf) -> World {
+	let text = fs::read_to_string(path).expect("Can not read world");
+	World::from_str(&text).expect("Invalid world")
+}
+
+fn read_registry(path: Option<&PathBuf>) -> PlayerRegistry {
+	path.map(|path| {
+		let text = fs::read_to_string(path).expect("Can not read player registry");
+		PlayerRegistry::from_str(&text).expect("Invalid player registry")
+	}).unwrap_or_default()
+}
+
+pub fn serve(args: ServeArgs){
+	let tokens = Server::parse_tokens(&fs::read_to_string(&args.tokens_file).expect("Can not read tokens file"));
+	let admin_token = read_token(&args.admin_token_file);
+	if admin_token.is_empty() {
+		eprintln!("Error: The admin token can not be empty");
+		std::process::exit(1);
 	}
-	
-	
-	#[test]
-	fn test_move(){
-		let mut world = World {field: Field::from_str(
-			"size:5,5; plot_size:10,10 ;;
-			5,5 keep:user;
-			1,1 raider;
-			1,2 raider;
-			1,3 raider;
-			1,4 raider;
-			1,5 raider;
-			1,6 raider;
-			1,7 raider;
-			1,8 raider;
-			1,9 raider;
-			2,1 raider;
-			7,7 stockpile;
-			9,9 road;
-			6,6 road;
-			2,9 road;
-			9,2 road;
-			0,1 road;
-			1,0 road;
-			
-			
-			15,4 keep:user;
-			11,6 raider;
-			
-			4,15 keep:other;
-			1,13 farm;
-			3,17 raider;
-			3,16 farm;"
-		).unwrap()};
-		world.update(&vec![
-			parse_commands("user", &[
-				"1,1 move 0,0",
-				"1,2 move 0,0",
-				"1,3 move 7,7",
-				"1,4 move 5,5",
-				
-				"1,5 move 6,6",
-				"1,6 move 9,9",
-				"1,7 move 9,2",
-				"1,8 move 2,9",
-				"1,0 move 1,0",
-				"2,1 move 19,9",
-			]),
-		]);
-		assert_eq!(world.field, Field::from_str(
-			"size:5,5; plot_size:10,10 ;;
-			5,5 keep:user;
-			0,0 raider;
-			1,2 raider;
-			1,3 raider;
-			1,4 raider;
-			1,5 raider;
-			1,6 raider;
-			10,2 raider;
-			1,8 raider;
-			1,9 raider;
-			2,1 raider;
-			7,7 stockpile;
-			9,9 road;
-			6,6 road;
-			2,9 road;
-			9,2 road;
-			0,1 road;
-			1,0 road;
-			
-			
-			15,4 keep:user;
-			11,6 raider;
-			
-			4,15 keep:other;
-			1,13 farm;
-			3,17 raider;
-			3,16 farm;"
-		).unwrap());
+	// A socket that is left over from a previous run would make binding fail
+	let _ = fs::remove_file(&args.socket);
+	let listener = UnixListener::bind(&args.socket).expect("Can not listen on socket");
+	Server::new(tokens, admin_token).serve(listener).expect("Server failed");
+}
+
+pub fn submit(args: SubmitArgs){
+	let token = read_token(&args.token_file);
+	let result = if args.feedback {
+		server::request(&args.socket, &format!("feedback {}", token), None)
+	} else {
+		let mut commands = String::new();
+		std::io::stdin().read_to_string(&mut commands).unwrap();
+		let kind = if args.standing { "standing" } else { "submit" };
+		server::request(&args.socket, &format!("{} {}", kind, token), Some(&commands))
+	};
+	match result {
+		Ok(lines) => for line in lines {
+			println!("{}", line);
+		}
+		Err(err) => {
+			eprintln!("Error: {}", err);
+			std::process::exit(1);
+		}
 	}
 }
 
+fn read_token(path: &PathBuf) -> String {
+	fs::read_to_string(path).expect("Can not read token file").trim().to_string()
+}
+
+
+pub fn read_all_commands<I: InputMethod>(input: &I, users: &[(UserId, I::IO)]) -> Vec<(UserId, Vec<Order>)>{
+	users.iter().filter_map(|(userid, connection)| {
+		let (source, command_text) = input.read_input(connection)?;
+		Some((userid.clone(), log_parse_errors(input, connection, parser::parse_input(&source, &command_text))))
+	}).collect()
+}
+
+pub fn read_all_standing_orders<I: InputMethod>(input: &I, users: &[(UserId, I::IO)]) -> Vec<(UserId, Vec<Order>)>{
+	users.iter().filter_map(|(userid, connection)| {
+		let (source, orders_text) = input.read_standing_orders(connection)?;
+		Some((userid.clone(), log_parse_errors(input, connection, parser::parse_standing_orders(&source, &orders_text))))
+	}).collect()
+}
+
+fn log_parse_errors<I: InputMethod, E: Display>(input: &I, connection: &I::IO, results: Vec<Result<Order, E>>) -> Vec<Order> {
+	results.into_iter().filter_map(|res| 
+		res.inspect_err(|pe| {
+			let _ = input.output(connection, &format!("Parse Error: {}", pe));
+		}).ok()
+	).collect()
+}
+
+pub fn write_report<I: InputMethod>(input: &I, users: &[(UserId, I::IO)], report: &Report){
+	for (userid, connection) in users {
+		for message in report.messages(userid) {
+			let _ = input.output(connection, &message);
+		}
+	}
+}
@@ -1,18 +1,191 @@
+use std::collections::HashMap;
 use std::str::FromStr;
 
 use crate::{
-	commands::Command,
-	errors::ParseError,
-	parse_err
+	commands::Order,
+	errors::{ParseError, SourceLocation},
+	parse_err,
+	partition,
+	utils::offset_in
 };
 
-pub fn parse_input(input: &str) -> Vec<Result<Command, ParseError>> {
-	input.split('\n').filter_map(|command_line| {
-		let command_text = command_line.trim();
+/// Parse all orders in a command file.
+/// Every line is parsed independently so all errors are reported, each with its line and column in `source`.
+///
+/// A block starting with "macro <name>" and ending with "end" defines a macro.
+/// A line with only the name of a macro is replaced by the orders in that macro.
+/// Macros can use other macros that were defined before them.
+pub fn parse_input(source: &str, input: &str) -> Vec<Result<Order, ParseError>> {
+	parse_lines(source, input, |text| text)
+}
+
+/// Standing orders are written like normal orders, optionally starting with "every turn:"
+pub fn parse_standing_orders(source: &str, input: &str) -> Vec<Result<Order, ParseError>> {
+	const PREFIX: &str = "every turn:";
+	parse_lines(source, input, |text| match text.get(..PREFIX.len()) {
+		Some(start) if start.eq_ignore_ascii_case(PREFIX) => text[PREFIX.len()..].trim_start(),
+		_ => text
+	})
+}
+
+/// Parse every line, after taking off what `strip` removes from the start of the line
+fn parse_lines(source: &str, input: &str, strip: fn(&str) -> &str) -> Vec<Result<Order, ParseError>> {
+	let mut results = Vec::new();
+	let mut macros: HashMap<String, Vec<Order>> = HashMap::new();
+	// name, line index and orders of the macro that is being defined
+	let mut definition: Option<(String, usize, Vec<Order>)> = None;
+	for (line_index, command_line) in input.split('\n').enumerate() {
+		let command_text = strip(command_line.trim());
 		if command_text.is_empty() || command_text.starts_with('#') {
-			return None
+			continue;
 		}
-		let command = Command::from_str(command_text);
-		Some(command.map_err(|e| parse_err!("Failed to parse '{}': {}", command_text, e.msg)))
-	}).collect()
+		let (keyword, name) = partition(command_text);
+		let parsed = match keyword.to_lowercase().as_str() {
+			"macro" => {
+				let name = name.trim().trim_end_matches(':');
+				if definition.is_some() {
+					Err(parse_err!("Macros can not be defined inside another macro"))
+				} else if name.is_empty() || name.contains(char::is_whitespace) {
+					Err(parse_err!("Invalid macro name '{}'", name).with_word(name))
+				} else {
+					definition = Some((name.to_string(), line_index, Vec::new()));
+					Ok(Vec::new())
+				}
+			}
+			"end" => {
+				if let Some((name, _, orders)) = definition.take() {
+					macros.insert(name, orders);
+					Ok(Vec::new())
+				} else {
+					Err(parse_err!("'end' without macro"))
+				}
+			}
+			_ => parse_line(command_text, &macros)
+		};
+		match parsed {
+			Ok(orders) => match &mut definition {
+				Some((_, _, body)) => body.extend(orders),
+				None => results.extend(orders.into_iter().map(Ok))
+			}
+			Err(e) => results.push(Err(locate(e, source, line_index, command_line, command_text)))
+		}
+	}
+	if let Some((name, line_index, _)) = definition {
+		let line = input.split('\n').nth(line_index).unwrap_or("");
+		results.push(Err(locate(parse_err!("Macro '{}' is not closed with 'end'", name), source, line_index, line, line.trim())));
+	}
+	results
+}
+
+fn parse_line(command_text: &str, macros: &HashMap<String, Vec<Order>>) -> Result<Vec<Order>, ParseError> {
+	if let Some(orders) = macros.get(command_text) {
+		return Ok(orders.clone());
+	}
+	if !command_text.contains(' ') && !command_text.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '@') {
+		let names: Vec<&String> = macros.keys().collect();
+		return Err(ParseError::invalid_word("macro", command_text, &names));
+	}
+	Ok(vec![Order::from_str(command_text)?])
+}
+
+/// Add the line and column to an error in `command_text`, which is a part of `command_line`.
+/// The column is that of the word the error is about, or else of the start of the command
+fn locate(e: ParseError, source: &str, line_index: usize, command_line: &str, command_text: &str) -> ParseError {
+	let column = |offset: usize| command_line[..offset].chars().count() + 1;
+	let start = offset_in(command_line, command_text).unwrap_or(0);
+	let column = if e.word_missing() {
+		// a missing word is reported after the end of the command
+		column(start + command_text.len()) + 1
+	} else {
+		e.word_offset(command_line).map_or(column(start), column)
+	};
+	let location = SourceLocation{source: source.to_string(), line: line_index + 1, column};
+	ParseError{msg: format!("Failed to parse '{}': {}", command_text, e.msg), ..e}.at(location)
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::commands::{Command, Action};
+
+	#[test]
+	fn test_diagnostics() {
+		let results = parse_input("commands.txt", "# comment\n3,3 build farm\n\n  3,3 build woodcuter\n3,3 atack north\n3,3 attack nrth\n1,1,1 use\n1,1 build\n1,1 build invalid");
+		assert_eq!(results.len(), 7);
+		assert!(results[0].is_ok());
+		let errors: Vec<String> = results.into_iter().skip(1).map(|r| r.unwrap_err().to_string()).collect();
+		assert_eq!(errors, vec![
+			"commands.txt:4:13: Failed to parse '3,3 build woodcuter': Invalid building 'woodcuter' (did you mean 'woodcutter'?)",
+			"commands.txt:5:5: Failed to parse '3,3 atack north': Invalid action 'atack' (did you mean 'attack'?)",
+			"commands.txt:6:12: Failed to parse '3,3 attack nrth': Invalid direction 'nrth' (did you mean 'north'?)",
+			"commands.txt:7:1: Failed to parse '1,1,1 use': Position must be 2 integers separated by a comma. Found '1,1,1'",
+			"commands.txt:8:11: Failed to parse '1,1 build': Invalid building ''",
+			"commands.txt:9:11: Failed to parse '1,1 build invalid': Invalid building 'invalid'",
+		]);
+		
+		// the column is that of the word that failed, not of an earlier word with the same text
+		let results = parse_input("commands.txt", "3,3 move 3,3x\nif empty 3,3: 3,3 attack 3,3\nfor each raider in plot 1,1: attack raider\n  offer 3 wood for 3 stone to 3 via 3");
+		let errors: Vec<String> = results.into_iter().map(|r| r.unwrap_err().to_string()).collect();
+		assert_eq!(errors, vec![
+			"commands.txt:1:10: Failed to parse '3,3 move 3,3x': Invalid Position '3,3x': invalid digit found in string",
+			"commands.txt:2:26: Failed to parse 'if empty 3,3: 3,3 attack 3,3': Invalid direction '3,3'",
+			"commands.txt:3:37: Failed to parse 'for each raider in plot 1,1: attack raider': Invalid direction 'raider'",
+			"commands.txt:4:37: Failed to parse 'offer 3 wood for 3 stone to 3 via 3': Position must be 2 integers separated by a comma. Found '3'",
+		]);
+		let results = parse_input("commands.txt", "offer 3 wood for 3 gold to alice via 19,5\naccept 3 wood for x stone from bob via 20,5\nmove 3,3x all raider in plot 1,1\nuse all raidr in plot 1,1");
+		let errors: Vec<String> = results.into_iter().map(|r| r.unwrap_err().to_string()).collect();
+		assert_eq!(errors, vec![
+			"commands.txt:1:20: Failed to parse 'offer 3 wood for 3 gold to alice via 19,5': Invalid resource 'gold'",
+			"commands.txt:2:19: Failed to parse 'accept 3 wood for x stone from bob via 20,5': Invalid amount 'x'",
+			"commands.txt:3:6: Failed to parse 'move 3,3x all raider in plot 1,1': Invalid Position '3,3x': invalid digit found in string",
+			"commands.txt:4:9: Failed to parse 'use all raidr in plot 1,1': Invalid entity 'raidr'",
+		]);
+	}
+
+	#[test]
+	fn test_macros() {
+		let results = parse_input("commands.txt", "
+			macro harvest
+				3,4 use
+				5,5 use
+			end
+			macro everything:
+				harvest
+				use all farm in plot 0,0
+			end
+			everything
+			harvst
+			1,1 use
+		");
+		let command = |x, y| Ok(Order::Command(Command{pos: crate::Pos::new(x, y).into(), action: Action::Use, condition: None}));
+		assert_eq!(results.len(), 5);
+		assert_eq!(results[0], command(3, 4));
+		assert_eq!(results[1], command(5, 5));
+		assert!(matches!(results[2], Ok(Order::ForEach{..})));
+		assert_eq!(results[3].as_ref().unwrap_err().to_string(), "commands.txt:11:4: Failed to parse 'harvst': Invalid macro 'harvst' (did you mean 'harvest'?)");
+		assert_eq!(results[4], command(1, 1));
+
+		let results = parse_input("commands.txt", "macro loop\nloop\nend\nmacro open\n1,1 use");
+		let errors: Vec<String> = results.into_iter().map(|r| r.unwrap_err().to_string()).collect();
+		assert_eq!(errors, vec![
+			"commands.txt:2:1: Failed to parse 'loop': Invalid macro 'loop'",
+			"commands.txt:4:1: Failed to parse 'macro open': Macro 'open' is not closed with 'end'",
+		]);
+	}
+
+	#[test]
+	fn test_standing_orders() {
+		let results = parse_standing_orders("standing.txt", "every turn: 3,4 use
+\tEvery turn: use all farm in plot 1,1
+5,5 use
+every turn: 3,4 usr");
+		assert_eq!(results[0], Ok(Order::Command(Command{pos: crate::Pos::new(3, 4).into(), action: Action::Use, condition: None})));
+		assert!(matches!(results[1], Ok(Order::ForEach{..})));
+		assert_eq!(results[2], Ok(Order::Command(Command{pos: crate::Pos::new(5, 5).into(), action: Action::Use, condition: None})));
+		assert_eq!(results[3].as_ref().unwrap_err().to_string(), "standing.txt:4:17: Failed to parse '3,4 usr': Invalid action 'usr' (did you mean 'use'?)");
+		let results = parse_standing_orders("standing.txt", "  EVERY TURN:  3,4 usr\nevery turn:");
+		assert_eq!(results[0].as_ref().unwrap_err().to_string(), "standing.txt:1:20: Failed to parse '3,4 usr': Invalid action 'usr' (did you mean 'use'?)");
+		assert_eq!(results.len(), 1);
+	}
 }
@@ -0,0 +1,438 @@
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::str::FromStr;
+
+use chrono::Utc;
+
+use crate::{
+	Pos,
+	UserId,
+	user::PlayerRegistry,
+	field::Field,
+	entity::Entity,
+	resources::Resource,
+	errors::ParseError,
+	parse_err
+};
+
+const TILE: i32 = 16;
+const RULER: i32 = 32;
+const LEGEND_WIDTH: i32 = 220;
+const LEGEND_LINE: i32 = 18;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+	Html,
+	Svg
+}
+
+impl FromStr for Format {
+	type Err = ParseError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.to_lowercase().as_str() {
+			"html" => Ok(Self::Html),
+			"svg" => Ok(Self::Svg),
+			_ => Err(parse_err!("Invalid render format '{}'", s))
+		}
+	}
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Colour {
+	pub r: u8,
+	pub g: u8,
+	pub b: u8
+}
+
+impl Colour {
+
+	pub fn new(r: u8, g: u8, b: u8) -> Colour {
+		Colour {r, g, b}
+	}
+
+	/// hue in degrees, saturation and lightness between 0 and 1
+	pub fn from_hsl(hue: f32, saturation: f32, lightness: f32) -> Colour {
+		let c = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+		let h = (hue % 360.0) / 60.0;
+		let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+		let (r, g, b) = match h as u32 {
+			0 => (c, x, 0.0),
+			1 => (x, c, 0.0),
+			2 => (0.0, c, x),
+			3 => (0.0, x, c),
+			4 => (x, 0.0, c),
+			_ => (c, 0.0, x)
+		};
+		let m = lightness - c / 2.0;
+		let channel = |v: f32| ((v + m) * 255.0).round() as u8;
+		Colour::new(channel(r), channel(g), channel(b))
+	}
+
+	pub fn hex(&self) -> String {
+		format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+	}
+}
+
+impl FromStr for Colour {
+	type Err = ParseError;
+
+	/// A colour in the form "#rrggbb"
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let digits = s.strip_prefix('#').filter(|d| d.len() == 6 && d.is_ascii())
+			.ok_or(parse_err!("Invalid colour '{}': must be of the form #rrggbb", s))?;
+		let channel = |i: usize| u8::from_str_radix(&digits[i..i+2], 16)
+			.map_err(|_| parse_err!("Invalid colour '{}': must be of the form #rrggbb", s));
+		Ok(Colour::new(channel(0)?, channel(2)?, channel(4)?))
+	}
+}
+
+const NEUTRAL: Colour = Colour {r: 0xee, g: 0xee, b: 0xee};
+
+/// The colour the player chose, or otherwise a stable colour so the same player has the same colour on every map
+fn player_colour(players: &PlayerRegistry, user: &UserId) -> Colour {
+	players.colour(user).unwrap_or_else(|| owner_colour(user))
+}
+
+pub fn owner_colour(user: &UserId) -> Colour {
+	let hash = user.0.bytes().fold(2_166_136_261u32, |h, b| (h ^ b as u32).wrapping_mul(16_777_619));
+	Colour::from_hsl((hash % 360) as f32, 0.6, 0.75)
+}
+
+fn darker(colour: Colour) -> Colour {
+	Colour::new(colour.r / 2, colour.g / 2, colour.b / 2)
+}
+
+fn tile_colour(field: &Field, players: &PlayerRegistry, pos: Pos) -> Colour {
+	let owner = field.plot_owner(pos);
+	let ground = owner.map(|owner| player_colour(players, &owner)).unwrap_or(NEUTRAL);
+	match field.get(pos) {
+		None => ground,
+		Some(Entity::Capital(_)) => Colour::new(0, 0, 0),
+		Some(Entity::Keep(owner)) => darker(player_colour(players, &owner)),
+		Some(Entity::Ruin) => Colour::new(0x5d, 0x40, 0x37),
+		Some(Entity::Forest) => Colour::new(0x2e, 0x7d, 0x32),
+		Some(Entity::Swamp) => Colour::new(0x55, 0x6b, 0x2f),
+		Some(Entity::Rock) => Colour::new(0x80, 0x80, 0x80),
+		Some(Entity::Wall) => Colour::new(0x6d, 0x4c, 0x41),
+		Some(Entity::Stockpile(_)) | Some(Entity::Road) | Some(Entity::Tradepost) => {
+			Colour::new(ground.r - ground.r / 5, ground.g - ground.g / 5, ground.b - ground.b / 5)
+		}
+		Some(ent) if ent.properties().movable || ent == Entity::Ram => Colour::new(0x8b, 0, 0),
+		Some(_) => Colour::new(ground.r - ground.r / 3, ground.g - ground.g / 3, ground.b - ground.b / 3)
+	}
+}
+
+fn glyph(ent: &Entity) -> &'static str {
+	match ent {
+		Entity::Capital(_) => "@",
+		Entity::Keep(_) => "$",
+		Entity::Ruin => "#",
+		Entity::Construction(_) => ":",
+		Entity::Raider => "r",
+		Entity::Warrior => "w",
+		Entity::Ram => "a",
+		Entity::Farm => "F",
+		Entity::Woodcutter => "W",
+		Entity::Quarry => "Q",
+		Entity::Lair => "L",
+		Entity::Barracks => "B",
+		Entity::Stockpile(None) => "_",
+		Entity::Stockpile(Some(Resource::Wood)) => "=",
+		Entity::Stockpile(Some(Resource::Stone)) => "*",
+		Entity::Stockpile(Some(Resource::Food)) => "%",
+		Entity::Stockpile(Some(Resource::Iron)) => "-",
+		Entity::Warehouse(_) => "H",
+		Entity::Road => "/",
+		Entity::Tradepost => "+",
+		Entity::Scoutpost => "!",
+		Entity::Wall => "X",
+		Entity::Forest => "&",
+		Entity::Swamp => "~",
+		Entity::Rock => "^",
+	}
+}
+
+const LEGEND_ENTITIES: &[(&str, &str)] = &[
+	("@", "capital"),
+	("$", "keep"),
+	("#", "ruin"),
+	("r w a", "raider, warrior, ram"),
+	("F W Q", "farm, woodcutter, quarry"),
+	("L B", "lair, barracks"),
+	("_", "empty stockpile"),
+	("H", "warehouse"),
+	("= * % -", "wood, stone, food, iron"),
+	("/ + !", "road, tradepost, scoutpost"),
+	("X", "barbarian wall"),
+	("& ~ ^", "forest, swamp, rock"),
+];
+
+fn escape(s: &str) -> String {
+	s.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+		.replace('"', "&quot;")
+		.replace('\'', "&#39;")
+}
+
+pub fn render(field: &Field, players: &PlayerRegistry, format: Format) -> String {
+	match format {
+		Format::Svg => render_svg(field, players),
+		Format::Html => render_html(field, players)
+	}
+}
+
+pub fn render_html(field: &Field, players: &PlayerRegistry) -> String {
+	format!(
+"<!doctype html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>Evil Cadastre</title>
+<style>
+body {{font-family: sans-serif;}}
+.tile:hover rect {{stroke: black; stroke-width: 2;}}
+</style>
+</head>
+<body>
+<h1>Evil Cadastre</h1>
+{}
+<p>Rendered at {}</p>
+</body>
+</html>
+", render_svg(field, players), Utc::now())
+}
+
+pub fn render_svg(field: &Field, players: &PlayerRegistry) -> String {
+	let plot_size = field.plot_size;
+	let size = field.size() * plot_size;
+	let map_width = size.x * TILE;
+	let map_height = size.y * TILE;
+
+	let mut owners: BTreeMap<String, (UserId, usize)> = BTreeMap::new();
+	for keep in field.list_keeps() {
+		if let Some(owner) = field.plot_owner(keep) {
+			owners.entry(players.display_name(&owner)).or_insert((owner, 0)).1 += 1;
+		}
+	}
+	let legend_height = (owners.len() + LEGEND_ENTITIES.len() + 3) as i32 * LEGEND_LINE;
+	let width = RULER + map_width + LEGEND_WIDTH;
+	let height = RULER + map_height.max(legend_height);
+
+	let mut svg = String::new();
+	let _ = writeln!(svg,
+		"<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" font-family=\"monospace\" font-size=\"{f}\">",
+		w=width, h=height, f=TILE - 4
+	);
+	let _ = writeln!(svg, "<rect width=\"{}\" height=\"{}\" fill=\"white\"/>", width, height);
+
+	// Rulers: the local coordinate of every tile and the absolute coordinate at the start of each plot
+	let _ = writeln!(svg, "<g class=\"rulers\" fill=\"#444\" font-size=\"{}\" text-anchor=\"middle\">", TILE / 2);
+	for x in 0..size.x {
+		let cx = RULER + x * TILE + TILE / 2;
+		let _ = writeln!(svg, "<text x=\"{}\" y=\"{}\">{}</text>", cx, RULER - 4, x % plot_size.x);
+		if x % plot_size.x == 0 {
+			let _ = writeln!(svg, "<text x=\"{}\" y=\"{}\" font-weight=\"bold\">{}</text>", cx, RULER - 4 - TILE / 2 - 2, x);
+		}
+	}
+	for y in 0..size.y {
+		let cy = RULER + y * TILE + TILE * 3 / 4;
+		let _ = writeln!(svg, "<text x=\"{}\" y=\"{}\">{}</text>", RULER - 6, cy, y % plot_size.y);
+		if y % plot_size.y == 0 {
+			let _ = writeln!(svg, "<text x=\"{}\" y=\"{}\" font-weight=\"bold\">{}</text>", RULER / 2 - 6, cy, y);
+		}
+	}
+	let _ = writeln!(svg, "</g>");
+
+	// Plots, coloured by owner
+	let _ = writeln!(svg, "<g class=\"plots\" stroke=\"black\" stroke-width=\"2\">");
+	for keep in field.list_keeps() {
+		let plot = keep / plot_size;
+		let owner = field.plot_owner(keep);
+		let colour = owner.as_ref().map(|owner| player_colour(players, owner)).unwrap_or(NEUTRAL);
+		let _ = write!(svg,
+			"<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"><title>plot {}",
+			RULER + plot.x * plot_size.x * TILE, RULER + plot.y * plot_size.y * TILE,
+			plot_size.x * TILE, plot_size.y * TILE, colour.hex(), plot
+		);
+		if let Some(owner) = owner {
+			let _ = write!(svg, " ({})", escape(&players.display_name(&owner)));
+		}
+		let _ = writeln!(svg, "</title></rect>");
+	}
+	let _ = writeln!(svg, "</g>");
+
+	// Thin lines between the tiles
+	let _ = write!(svg, "<path stroke=\"#000\" stroke-opacity=\"0.1\" d=\"");
+	for x in 1..size.x {
+		let _ = write!(svg, "M{} {}v{}", RULER + x * TILE, RULER, map_height);
+	}
+	for y in 1..size.y {
+		let _ = write!(svg, "M{} {}h{}", RULER, RULER + y * TILE, map_width);
+	}
+	let _ = writeln!(svg, "\"/>");
+
+	// Entities, with a tooltip describing the tile
+	let _ = writeln!(svg, "<g class=\"tiles\" text-anchor=\"middle\">");
+	for y in 0..size.y {
+		for x in 0..size.x {
+			let pos = Pos::new(x, y);
+			let ent = match field.get(pos) {
+				Some(ent) => ent,
+				None => continue
+			};
+			let (fill, weight) = match &ent {
+				Entity::Capital(owner) => (darker(player_colour(players, owner)).hex(), "bold"),
+				Entity::Keep(owner) => (darker(player_colour(players, owner)).hex(), "normal"),
+				_ => ("none".to_string(), "normal")
+			};
+			let text_colour = if fill == "none" {"black"} else {"white"};
+			let _ = writeln!(svg,
+				"<g class=\"tile\"><title>{} {}</title><rect x=\"{}\" y=\"{}\" width=\"{t}\" height=\"{t}\" fill=\"{}\"/><text x=\"{}\" y=\"{}\" fill=\"{}\" font-weight=\"{}\">{}</text></g>",
+				pos, escape(&ent.to_string()),
+				RULER + x * TILE, RULER + y * TILE, fill,
+				RULER + x * TILE + TILE / 2, RULER + y * TILE + TILE * 3 / 4, text_colour, weight,
+				escape(glyph(&ent)),
+				t=TILE
+			);
+		}
+	}
+	let _ = writeln!(svg, "</g>");
+
+	// Legend
+	let legend_x = RULER + map_width + TILE;
+	let mut line_y = RULER;
+	let _ = writeln!(svg, "<g class=\"legend\">");
+	let _ = writeln!(svg, "<text x=\"{}\" y=\"{}\" font-weight=\"bold\">Players</text>", legend_x, line_y);
+	for (name, (owner, plots)) in owners.iter() {
+		line_y += LEGEND_LINE;
+		let _ = writeln!(svg,
+			"<rect x=\"{}\" y=\"{}\" width=\"{t}\" height=\"{t}\" fill=\"{}\" stroke=\"black\"/><text x=\"{}\" y=\"{}\">{} ({} plots)</text>",
+			legend_x, line_y - TILE + 3, player_colour(players, owner).hex(), legend_x + TILE + 6, line_y, escape(name), plots,
+			t=TILE
+		);
+	}
+	line_y += LEGEND_LINE * 2;
+	let _ = writeln!(svg, "<text x=\"{}\" y=\"{}\" font-weight=\"bold\">Tiles</text>", legend_x, line_y);
+	for (glyphs, description) in LEGEND_ENTITIES {
+		line_y += LEGEND_LINE;
+		let _ = writeln!(svg,
+			"<text x=\"{}\" y=\"{}\">{}</text><text x=\"{}\" y=\"{}\">{}</text>",
+			legend_x, line_y, escape(glyphs), legend_x + TILE * 5, line_y, description
+		);
+	}
+	let _ = writeln!(svg, "</g>");
+
+	let _ = writeln!(svg, "</svg>");
+	svg
+}
+
+/// A minimap with a block of `scale` by `scale` pixels for every tile
+pub fn render_png(field: &Field, players: &PlayerRegistry, scale: u32) -> Result<Vec<u8>, png::EncodingError> {
+	let plot_size = field.plot_size;
+	let size = field.size() * plot_size;
+	let scale = scale.max(1) as usize;
+	let width = size.x as usize * scale;
+	let height = size.y as usize * scale;
+	let mut pixels = vec![0u8; width * height * 3];
+	for y in 0..size.y {
+		for x in 0..size.x {
+			let pos = Pos::new(x, y);
+			let colour = tile_colour(field, players, pos);
+			let border = darker(colour);
+			for dy in 0..scale {
+				for dx in 0..scale {
+					// outline the plots when there is room for it
+					let on_border = scale >= 3 && (
+						(x % plot_size.x == 0 && dx == 0) || (y % plot_size.y == 0 && dy == 0)
+					);
+					let c = if on_border {border} else {colour};
+					let i = ((y as usize * scale + dy) * width + x as usize * scale + dx) * 3;
+					pixels[i] = c.r;
+					pixels[i + 1] = c.g;
+					pixels[i + 2] = c.b;
+				}
+			}
+		}
+	}
+	let mut data = Vec::new();
+	{
+		let mut encoder = png::Encoder::new(&mut data, width as u32, height as u32);
+		encoder.set_color(png::ColorType::RGB);
+		encoder.set_depth(png::BitDepth::Eight);
+		let mut writer = encoder.write_header()?;
+		writer.write_image_data(&pixels)?;
+	}
+	Ok(data)
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_hsl() {
+		assert_eq!(Colour::from_hsl(0.0, 1.0, 0.5), Colour::new(255, 0, 0));
+		assert_eq!(Colour::from_hsl(120.0, 1.0, 0.5), Colour::new(0, 255, 0));
+		assert_eq!(Colour::from_hsl(240.0, 1.0, 0.5), Colour::new(0, 0, 255));
+		assert_eq!(Colour::from_hsl(0.0, 0.0, 1.0).hex(), "#ffffff");
+	}
+
+	#[test]
+	fn test_svg() {
+		let field = Field::from_str(
+			"size:2,2; plot_size:10,10 ;;
+			5,5 capital:<evil>;
+			3,3 farm;"
+		).unwrap();
+		let svg = render_svg(&field, &PlayerRegistry::default());
+		assert!(svg.starts_with("<svg "));
+		assert!(svg.contains("<title>5,5 capital:&lt;evil&gt;</title>"));
+		assert!(svg.contains("<title>3,3 farm</title>"));
+		assert!(svg.contains("&lt;evil&gt; (1 plots)"));
+		assert!(svg.contains(&owner_colour(&UserId("<evil>".to_string())).hex()));
+
+		let players = PlayerRegistry::from_str("<evil>; joined:2026-10-01; display_name:The Evil One; colour:#123456").unwrap();
+		let svg = render_svg(&field, &players);
+		assert!(svg.contains("The Evil One (1 plots)"));
+		assert!(svg.contains("#123456"));
+		assert!(!svg.contains(&owner_colour(&UserId("<evil>".to_string())).hex()));
+	}
+
+	#[test]
+	fn test_colour_parsing() {
+		assert_eq!(Colour::from_str("#ff8001"), Ok(Colour::new(255, 128, 1)));
+		assert_eq!(Colour::from_str(&Colour::new(1, 2, 3).hex()), Ok(Colour::new(1, 2, 3)));
+		assert!(Colour::from_str("ff8001").is_err());
+		assert!(Colour::from_str("#ff80").is_err());
+		assert!(Colour::from_str("#gg8001").is_err());
+		assert!(Colour::from_str("#ff80€").is_err());
+	}
+
+	#[test]
+	fn test_png() {
+		let field = Field::from_str(
+			"size:3,2; plot_size:10,10 ;;
+			5,5 capital:evil;
+			15,4 keep:evil;"
+		).unwrap();
+		let data = render_png(&field, &PlayerRegistry::default(), 2).unwrap();
+		let (info, mut reader) = png::Decoder::new(data.as_slice()).read_info().unwrap();
+		assert_eq!((info.width, info.height), (60, 40));
+		let mut pixels = vec![0; info.buffer_size()];
+		reader.next_frame(&mut pixels).unwrap();
+		let pixel = |x: usize, y: usize| {
+			let i = (y * 60 + x) * 3;
+			Colour::new(pixels[i], pixels[i+1], pixels[i+2])
+		};
+		let evil = owner_colour(&UserId("evil".to_string()));
+		assert_eq!(pixel(10, 10), Colour::new(0, 0, 0));
+		assert_eq!(pixel(30, 8), darker(evil));
+		assert_eq!(pixel(1, 1), evil);
+		assert_eq!(pixel(45, 25), NEUTRAL);
+	}
+}
@@ -0,0 +1,37 @@
+use crate::UserId;
+
+/// Everything players should be told about a turn
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Report {
+	/// Messages in the order they were added, with their recipient or None for everyone
+	messages: Vec<(Option<UserId>, String)>
+}
+
+impl Report {
+	
+	pub fn add(&mut self, user: &UserId, message: String) {
+		self.messages.push((Some(user.clone()), message));
+	}
+	
+	/// A message for every player, including players that did not send any commands
+	pub fn broadcast(&mut self, message: String) {
+		self.messages.push((None, message));
+	}
+	
+	pub fn messages(&self, user: &UserId) -> Vec<String> {
+		self.messages.iter()
+			.filter(|(recipient, _)| recipient.as_ref().is_none_or(|recipient| recipient == user))
+			.map(|(_, message)| message.clone())
+			.collect()
+	}
+	
+	pub fn users(&self) -> Vec<UserId> {
+		let mut users: Vec<UserId> = Vec::new();
+		for user in self.messages.iter().filter_map(|(recipient, _)| recipient.as_ref()) {
+			if !users.contains(user) {
+				users.push(user.clone());
+			}
+		}
+		users
+	}
+}
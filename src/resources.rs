@@ -3,10 +3,7 @@ use std::collections::HashMap;
 use std::fmt;
 use std::str::FromStr;
 
-use crate::{
-	errors::ParseError,
-	parse_err,
-};
+use crate::errors::ParseError;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Resource {
@@ -80,7 +77,7 @@ impl FromStr for Resource {
 			"wood" => Ok(Self::Wood),
 			"stone" => Ok(Self::Stone),
 			"iron" => Ok(Self::Iron),
-			_ => Err(parse_err!("Invalid resource '{}'", s))
+			_ => Err(ParseError::invalid_word("resource", s, &["food", "wood", "stone", "iron"]))
 		}
 	}
 }
@@ -0,0 +1,116 @@
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{
+	field::Field,
+	entity::Entity,
+	trade::Amount,
+	scoring,
+	rules,
+	errors::ParseError,
+	parse_err,
+	Pos
+};
+
+/// What happens to players that lost their capital, written as "cooldown 5, spawn, 5 wood, 5 food"
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RespawnPolicy {
+	/// The number of turns after the elimination before the player can claim again
+	pub cooldown: u64,
+	/// Whether the engine chooses the plot of a respawning player, instead of the plot in the claim
+	pub spawn: bool,
+	/// The resources a respawning player starts with
+	pub bundle: Vec<Amount>
+}
+
+impl RespawnPolicy {
+
+	/// The first turn a player that was eliminated at `eliminated` can claim again
+	pub fn respawn_turn(&self, eliminated: u64) -> u64 {
+		eliminated + self.cooldown.max(1)
+	}
+}
+
+impl FromStr for RespawnPolicy {
+	type Err = ParseError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let mut policy = Self::default();
+		for part in s.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+			if let Some(turns) = part.strip_prefix("cooldown ") {
+				policy.cooldown = turns.trim().parse().map_err(|_| parse_err!("Invalid number of turns '{}'", turns))?;
+			} else if part == "spawn" {
+				policy.spawn = true;
+			} else {
+				policy.bundle.push(Amount::from_str(part)?);
+			}
+		}
+		Ok(policy)
+	}
+}
+
+impl fmt::Display for RespawnPolicy {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let mut parts = vec![format!("cooldown {}", self.cooldown)];
+		if self.spawn {
+			parts.push("spawn".to_string());
+		}
+		parts.extend(self.bundle.iter().map(Amount::to_string));
+		write!(f, "{}", parts.join(", "))
+	}
+}
+
+/// The plot that can be claimed that is furthest from strong players.
+/// Every owned plot counts with the score of its owner, divided by its distance
+pub fn spawn_plot(field: &Field) -> Option<Pos> {
+	let scores = scoring::scores(field);
+	let owned: Vec<(Pos, usize)> = field.list_keeps().into_iter().filter_map(|keep| {
+		let owner = field.plot_owner(keep)?;
+		let (_, score) = scores.iter().find(|(user, _)| user == &owner)?;
+		Some((keep, score.total()))
+	}).collect();
+	field.list_keeps().into_iter()
+		.filter(|keep| rules::can_claim(field, *keep))
+		.min_by_key(|keep| owned.iter().map(|(pos, score)| score * 1000 / (keep.distance_to(*pos) as usize + 1)).sum::<usize>())
+}
+
+/// Put the starting resources in new stockpiles around the keep
+pub fn place_bundle(field: &mut Field, keep: Pos, bundle: &[Amount]) {
+	for amount in bundle {
+		for _ in 0..amount.count {
+			if let Some(pos) = field.find(keep, None) {
+				field.set_tile(pos, Entity::Stockpile(Some(amount.resource)));
+			}
+		}
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::resources::Resource;
+
+	#[test]
+	fn test_respawn_policy() {
+		let policy = RespawnPolicy::from_str("cooldown 3, spawn, 2 wood, 1 food").unwrap();
+		assert_eq!(policy, RespawnPolicy{
+			cooldown: 3,
+			spawn: true,
+			bundle: vec![Amount{count: 2, resource: Resource::Wood}, Amount{count: 1, resource: Resource::Food}]
+		});
+		assert_eq!(policy.to_string(), "cooldown 3, spawn, 2 wood, 1 food");
+		assert!(RespawnPolicy::from_str("cooldown soon").is_err());
+
+		let mut field = Field::from_str("size:3,3; plot_size:10,10;; 5,5 capital:alice; 15,4 keep:alice; 24,15 ruin; 5,25 capital:bob;").unwrap();
+		assert_eq!(spawn_plot(&field), Some(Pos::new(25, 25)));
+		let mut crowded = field.clone();
+		crowded.set_tile(Pos::new(24, 15), Entity::Keep(crate::UserId("carol".to_string())));
+		// every other free plot is next to a keep
+		assert_eq!(spawn_plot(&crowded), Some(Pos::new(15, 24)));
+		place_bundle(&mut field, Pos::new(25, 25), &policy.bundle);
+		let resources = field.tiles_in_plot(Pos::new(25, 25)).into_iter().filter_map(|pos| field.get(pos)).count();
+		assert_eq!(resources, 3);
+	}
+}
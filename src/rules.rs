@@ -7,7 +7,9 @@ use crate::{
 	entity::Entity,
 	resources::{Resource, ResourceCount},
 	Pos,
-	locations::Direction
+	locations::Direction,
+	diplomacy::Alliances,
+	trade::Amount
 };
 
 
@@ -16,28 +18,68 @@ pub fn claim_first_keep(field: &mut Field, source_pos: Pos, userid: UserId) -> O
 		return None;
 	}
 	let pos = field.keep_location(source_pos);
-	if field.get(pos).is_some() {
+	if !can_claim(field, pos) {
 		return None
 	}
-	for dir in Direction::directions() {
-		if let Some(Entity::Keep(_)) = field.get(field.keep_location(pos + dir.to_pos() * field.plot_size)) {
-			return None
-		}
-	}
 	field.set_tile(pos, Entity::Capital(userid));
 	Some(pos)
 }
 
-pub fn pay(field: &mut Field, pos: Pos, cost: &ResourceCount) -> bool {
+/// Whether the plot of the keep at `pos` is free and not next to the keep of another player
+pub fn can_claim(field: &Field, pos: Pos) -> bool {
+	if field.get(pos).is_some_and(|ent| ent != Entity::Ruin) {
+		return false
+	}
+	Direction::directions().into_iter().all(|dir|
+		!matches!(field.get(field.keep_location(pos + dir.to_pos() * field.plot_size)), Some(Entity::Keep(_)))
+	)
+}
+
+/// The number of turns after relocating a capital before it can be relocated again
+pub const RELOCATION_COOLDOWN: u64 = 10;
+
+/// The cost of taking over a plot with a scoutpost, unless the world sets another
+pub fn default_takeover_cost() -> Vec<Amount> {
+	vec![
+		Amount{count: 10, resource: Resource::Wood},
+		Amount{count: 5, resource: Resource::Food},
+		Amount{count: 5, resource: Resource::Stone}
+	]
+}
+
+pub fn relocation_cost() -> ResourceCount {
+	ResourceCount::from_vec(&[
+		Resource::Wood, Resource::Wood, Resource::Wood, Resource::Wood, Resource::Wood,
+		Resource::Stone, Resource::Stone, Resource::Stone, Resource::Stone, Resource::Stone,
+		Resource::Food, Resource::Food, Resource::Food, Resource::Food, Resource::Food
+	])
+}
+
+/// The cost of training a unit in this building, or None if the building does not train units
+pub fn training_cost(building: &Entity) -> Option<ResourceCount> {
+	match building {
+		Entity::Lair => Some(ResourceCount::from_vec(&[Resource::Food, Resource::Food, Resource::Food])),
+		// todo: will require iron later
+		Entity::Barracks => Some(ResourceCount::from_vec(&[Resource::Food, Resource::Food, Resource::Food, Resource::Food, Resource::Food, Resource::Wood, Resource::Stone])),
+		_ => None
+	}
+}
+
+/// All resources stored in the plot of pos
+pub fn available_resources(field: &Field, pos: Pos) -> ResourceCount {
 	let mut available_resources = ResourceCount::default();
 	for pos in field.tiles_in_plot(pos){
-		if let Some(Entity::Stockpile(Some(res))) = field.get(pos) {
+		for res in field.get(pos).map(|ent| ent.stored_resources()).unwrap_or_default() {
 			available_resources.add_resource(res);
 		}
 	}
-	if available_resources.can_afford(cost) {
+	available_resources
+}
+
+pub fn pay(field: &mut Field, pos: Pos, cost: &ResourceCount) -> bool {
+	if available_resources(field, pos).can_afford(cost) {
 		for res in cost.to_vec() {
-			field.change_tile(pos, Some(Entity::Stockpile(Some(res))), Some(Entity::Stockpile(None)));
+			take_resource(field, pos, res);
 		}
 		return true;
 	}
@@ -45,30 +87,130 @@ pub fn pay(field: &mut Field, pos: Pos, cost: &ResourceCount) -> bool {
 }
 
 
-pub fn move_unit_destination(field: &Field, from: Pos, to: Pos) -> Option<Pos> {
+/// Take a resource from the nearest stockpile or warehouse that has it
+fn take_resource(field: &mut Field, pos: Pos, res: Resource) -> Option<Pos> {
+	let tile = field.tiles_in_plot(pos).into_iter().find(|p| field.get(*p).is_some_and(|ent| ent.stored_resources().contains(&res)))?;
+	match field.get(tile) {
+		Some(Entity::Warehouse(mut stored)) => {
+			let index = stored.iter().position(|r| *r == res)?;
+			stored.remove(index);
+			field.set_tile(tile, Entity::Warehouse(stored));
+		}
+		_ => field.set_tile(tile, Entity::Stockpile(None))
+	}
+	Some(tile)
+}
+
+/// Put a resource on a tile that has room for it
+fn store(field: &mut Field, tile: Pos, res: Resource) {
+	match field.get(tile) {
+		Some(Entity::Warehouse(mut stored)) => {
+			stored.push(res);
+			field.set_tile(tile, Entity::Warehouse(stored));
+		}
+		_ => field.set_tile(tile, Entity::Stockpile(Some(res)))
+	}
+}
+
+/// The number of resources that can still be stored in the plot
+pub fn free_storage(field: &Field, pos: Pos) -> usize {
+	field.tiles_in_plot(pos).into_iter().filter_map(|p| field.get(p)).map(|ent| ent.storage_room()).sum()
+}
+
+/// The nearest tile in the plot with room for a resource
+fn storage_with_room(field: &Field, pos: Pos) -> Option<Pos> {
+	field.tiles_in_plot(pos).into_iter().find(|p| field.get(*p).is_some_and(|ent| ent.storage_room() > 0))
+}
+
+/// The tile on the other side of the border where a unit or resource arrives, if that plot is friendly
+fn cross_pos(field: &Field, alliances: &Alliances, to: Pos, arrival: impl Fn(&Field, Pos) -> Option<Pos>) -> Option<Pos> {
+	let pos = arrival(field, field.across_border(to)?)?;
+	if alliances.friendly(&field.plot_owner(pos), &field.plot_owner(to)) {
+		Some(pos)
+	} else {
+		None
+	}
+}
+
+pub fn move_unit_destination(field: &Field, alliances: &Alliances, from: Pos, to: Pos) -> Option<Pos> {
 	if field.keep_location(from) != field.keep_location(to) {
 		return None;
 	}
 	match field.get(to) {
-		Some(Entity::Road) => field.cross_pos(to),
+		Some(Entity::Road) => cross_pos(field, alliances, to, |field, pos| field.find(pos, None)),
 		Some(_) => None,
 		None => Some(to)
 	}
 }
 
-pub fn move_resource_destination(field: &Field, from: Pos, to: Pos) -> Option<Pos> {
+pub fn move_resource_destination(field: &Field, alliances: &Alliances, from: Pos, to: Pos) -> Option<Pos> {
 	if field.keep_location(from) != field.keep_location(to) {
 		return None;
 	}
 	match field.get(to) {
-		Some(Entity::Tradepost) => field.cross_pos(to),
-		Some(Entity::Stockpile(None)) => Some(to),
+		// Resources arrive in the nearest stockpile or warehouse on the other side
+		Some(Entity::Tradepost) => cross_pos(field, alliances, to, storage_with_room),
+		Some(ent) if ent.storage_room() > 0 => Some(to),
 		_ => None
 	}
 }
 
+/// Move a resource in the stockpile or warehouse at `from` to a destination from `move_resource_destination`.
+/// A warehouse gives the resource that was stored in it first
+pub fn move_resource(field: &mut Field, from: Pos, to: Pos) {
+	if let Some(res) = field.get(from).and_then(|ent| ent.stored_resources().first().copied()) {
+		take_resource(field, from, res);
+		store(field, to, res);
+	}
+}
+
+/// Store a resource in the nearest stockpile or warehouse with room for it
 pub fn add_resource(field: &mut Field, pos: Pos, res: Resource) -> Option<Pos> {
-	field.change_tile(pos, Some(Entity::Stockpile(None)), Some(Entity::Stockpile(Some(res))))
+	let tile = storage_with_room(field, pos)?;
+	store(field, tile, res);
+	Some(tile)
+}
+
+/// Pay `food` for every unit in the plot, starting with the units nearest to the keep. Units that can not be fed desert.
+/// Returns the number of deserted units
+pub fn upkeep(field: &mut Field, keep: Pos, food: usize) -> usize {
+	let cost = ResourceCount::from_vec(&vec![Resource::Food; food]);
+	let mut deserted = 0;
+	for pos in field.tiles_in_plot(keep) {
+		if !field.get(pos).is_some_and(|ent| ent.properties().movable) {
+			continue;
+		}
+		if !pay(field, keep, &cost) {
+			field.clear_tile(pos);
+			deserted += 1;
+		}
+	}
+	deserted
+}
+
+/// Turn all keeps and the capital of a player into ruins. Returns the number of abandoned plots
+pub fn abandon(field: &mut Field, user: &UserId) -> usize {
+	let keeps: Vec<Pos> = field.list_keeps().into_iter().filter(|keep| field.plot_owner(*keep).as_ref() == Some(user)).collect();
+	for keep in keeps.iter() {
+		field.set_tile(*keep, Entity::Ruin);
+	}
+	keeps.len()
+}
+
+/// Let one building, unit or stockpile in every ruined plot fall apart
+pub fn crumble(field: &mut Field) {
+	for keep in field.list_keeps() {
+		if field.get(keep) != Some(Entity::Ruin) {
+			continue;
+		}
+		let crumbling = field.tiles_in_plot(keep).into_iter().find(|pos| match field.get(*pos) {
+			None | Some(Entity::Forest) | Some(Entity::Swamp) | Some(Entity::Rock) => false,
+			Some(_) => true
+		});
+		if let Some(pos) = crumbling {
+			field.clear_tile(pos);
+		}
+	}
 }
 
 pub fn destroy_keep(field: &mut Field, pos: Pos) -> Option<()> {
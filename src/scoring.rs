@@ -0,0 +1,200 @@
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{
+	UserId,
+	entity::Entity,
+	field::Field,
+	errors::ParseError,
+	parse_err
+};
+
+const PLOT_POINTS: usize = 10;
+const CAPITAL_POINTS: usize = 20;
+const RESOURCE_POINTS: usize = 1;
+const UNIT_POINTS: usize = 3;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Score {
+	pub plots: usize,
+	pub capitals: usize,
+	pub resources: usize,
+	pub units: usize
+}
+
+impl Score {
+	pub fn total(&self) -> usize {
+		self.plots * PLOT_POINTS + self.capitals * CAPITAL_POINTS + self.resources * RESOURCE_POINTS + self.units * UNIT_POINTS
+	}
+}
+
+/// The score of every player that owns a plot, from highest to lowest. The barbarians don't count
+pub fn scores(field: &Field) -> Vec<(UserId, Score)> {
+	let mut scores: HashMap<UserId, Score> = HashMap::new();
+	for keep in field.list_keeps() {
+		let owner = match field.plot_owner(keep) {
+			Some(owner) if !owner.is_barbarians() => owner,
+			_ => continue
+		};
+		let score = scores.entry(owner).or_default();
+		score.plots += 1;
+		if let Some(Entity::Capital(_)) = field.get(keep) {
+			score.capitals += 1;
+		}
+		for ent in field.tiles_in_plot(keep).into_iter().filter_map(|pos| field.get(pos)) {
+			match ent {
+				ent if ent.properties().movable => score.units += 1,
+				ent => score.resources += ent.stored_resources().len()
+			}
+		}
+	}
+	let mut scores: Vec<(UserId, Score)> = scores.into_iter().collect();
+	scores.sort_by(|(a, a_score), (b, b_score)| b_score.total().cmp(&a_score.total()).then_with(|| a.0.cmp(&b.0)));
+	scores
+}
+
+pub fn leaderboard(field: &Field, turn: u64) -> String {
+	let mut board = format!("Leaderboard after turn {}\n", turn);
+	board.push_str("rank  score  plots  capitals  resources  units  player\n");
+	for (rank, (user, score)) in scores(field).into_iter().enumerate() {
+		board.push_str(&format!(
+			"{:>4}  {:>5}  {:>5}  {:>8}  {:>9}  {:>5}  {}\n",
+			rank + 1, score.total(), score.plots, score.capitals, score.resources, score.units, user.0
+		));
+	}
+	board
+}
+
+/// A way to win the game, which ends the season
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VictoryCondition {
+	/// "plots <percentage>": hold at least this percentage of all plots
+	Plots(usize),
+	/// "last capital": be the only player with a capital left, after there were others
+	LastCapital,
+	/// "score after <turns>": have the highest score when this turn is reached
+	ScoreAfter(u64)
+}
+
+impl VictoryCondition {
+
+	/// The winner and a description of the victory, if this condition is reached.
+	/// `capitals_before` is the number of capitals before the turn
+	pub fn winner(&self, field: &Field, turn: u64, capitals_before: usize) -> Option<(UserId, String)> {
+		match self {
+			Self::Plots(percentage) => {
+				let total = field.list_keeps().len();
+				scores(field).into_iter()
+					.find(|(_, score)| score.plots * 100 >= percentage * total)
+					.map(|(user, score)| (user, format!("holding {} of {} plots", score.plots, total)))
+			}
+			Self::LastCapital => {
+				let capitals = capital_owners(field);
+				if capitals_before > 1 && capitals.len() == 1 {
+					Some((capitals[0].clone(), "having the last capital standing".to_string()))
+				} else {
+					None
+				}
+			}
+			Self::ScoreAfter(turns) => {
+				if turn < *turns {
+					return None;
+				}
+				scores(field).into_iter()
+					.next()
+					.map(|(user, score)| (user, format!("having the highest score ({}) after {} turns", score.total(), turns)))
+			}
+		}
+	}
+}
+
+pub fn capital_owners(field: &Field) -> Vec<UserId> {
+	field.list_keeps().into_iter().filter_map(|keep| match field.get(keep) {
+		Some(Entity::Capital(owner)) => Some(owner),
+		_ => None
+	}).collect()
+}
+
+impl FromStr for VictoryCondition {
+	type Err = ParseError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let s = s.trim().to_lowercase();
+		if let Some(percentage) = s.strip_prefix("plots ") {
+			let percentage = percentage.trim().trim_end_matches('%').parse()
+				.ok()
+				.filter(|p| *p > 0 && *p <= 100)
+				.ok_or_else(|| parse_err!("Invalid percentage '{}'", percentage))?;
+			return Ok(Self::Plots(percentage));
+		}
+		if let Some(turns) = s.strip_prefix("score after ") {
+			return Ok(Self::ScoreAfter(turns.trim().parse().map_err(|_| parse_err!("Invalid number of turns '{}'", turns))?));
+		}
+		if s == "last capital" {
+			return Ok(Self::LastCapital);
+		}
+		Err(parse_err!("Invalid victory condition '{}'. Expected 'plots <percentage>', 'last capital' or 'score after <turns>'", s))
+	}
+}
+
+impl fmt::Display for VictoryCondition {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Plots(percentage) => write!(f, "plots {}", percentage),
+			Self::LastCapital => write!(f, "last capital"),
+			Self::ScoreAfter(turns) => write!(f, "score after {}", turns)
+		}
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn user(name: &str) -> UserId {
+		UserId(name.to_string())
+	}
+
+	#[test]
+	fn test_scores() {
+		let field = Field::from_str(
+			"size:2,2; plot_size:10,10 ;;
+			5,5 capital:alice;
+			3,3 stockpile:wood;
+			3,4 stockpile:food;
+			3,5 stockpile;
+			4,4 raider;
+			15,4 keep:alice;
+			4,15 capital:bob;
+			2,12 warrior;
+			2,13 farm;"
+		).unwrap();
+		let scores = scores(&field);
+		assert_eq!(scores, vec![
+			(user("alice"), Score{plots: 2, capitals: 1, resources: 2, units: 1}),
+			(user("bob"), Score{plots: 1, capitals: 1, resources: 0, units: 1}),
+		]);
+		assert_eq!(scores[0].1.total(), 2 * PLOT_POINTS + CAPITAL_POINTS + 2 * RESOURCE_POINTS + UNIT_POINTS);
+		assert!(leaderboard(&field, 3).contains("   2     33      1         1          0      1  bob\n"));
+
+		let plots = |p: &str| VictoryCondition::from_str(p).unwrap().winner(&field, 3, 2).map(|(user, _)| user);
+		assert_eq!(plots("plots 50%"), Some(user("alice")));
+		assert_eq!(plots("plots 51"), None);
+		assert_eq!(plots("last capital"), None);
+		assert_eq!(plots("score after 3"), Some(user("alice")));
+		assert_eq!(plots("score after 4"), None);
+		let conquered = Field::from_str("size:2,2; plot_size:10,10 ;; 5,5 capital:alice; 4,15 keep:alice;").unwrap();
+		assert_eq!(VictoryCondition::LastCapital.winner(&conquered, 3, 2).map(|(user, _)| user), Some(user("alice")));
+		assert_eq!(VictoryCondition::LastCapital.winner(&conquered, 3, 1), None);
+
+		for condition in &["plots 60", "last capital", "score after 100"] {
+			assert_eq!(VictoryCondition::from_str(condition).unwrap().to_string(), *condition);
+		}
+		assert!(VictoryCondition::from_str("plots 0").is_err());
+		assert!(VictoryCondition::from_str("plots 101").is_err());
+		assert!(VictoryCondition::from_str("most gold").is_err());
+	}
+}
@@ -0,0 +1,153 @@
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{
+	world::World,
+	scoring,
+	errors::{ParseError, CommandError},
+	command_err
+};
+
+/// The phases of a season, in the order they normally happen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Phase {
+	/// Players can only claim their first plot, and no turns are counted
+	Registration,
+	#[default]
+	Running,
+	/// No commands are executed until the season is started again
+	Paused,
+	/// The season is over, either by a victory or by an administrator
+	Ended
+}
+
+impl FromStr for Phase {
+	type Err = ParseError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.trim() {
+			"registration" => Ok(Self::Registration),
+			"running" => Ok(Self::Running),
+			"paused" => Ok(Self::Paused),
+			"ended" => Ok(Self::Ended),
+			s => Err(ParseError::invalid_word("phase", s, &["registration", "running", "paused", "ended"]))
+		}
+	}
+}
+
+impl fmt::Display for Phase {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", match self {
+			Self::Registration => "registration",
+			Self::Running => "running",
+			Self::Paused => "paused",
+			Self::Ended => "ended"
+		})
+	}
+}
+
+/// Start play after registration, or continue after a pause
+pub fn start(world: &mut World) -> Result<(), CommandError> {
+	match world.phase {
+		Phase::Registration | Phase::Paused => {
+			world.phase = Phase::Running;
+			Ok(())
+		}
+		phase => Err(command_err!("A season can not be started when it is {}", phase))
+	}
+}
+
+pub fn pause(world: &mut World) -> Result<(), CommandError> {
+	match world.phase {
+		Phase::Running => {
+			world.phase = Phase::Paused;
+			Ok(())
+		}
+		phase => Err(command_err!("Only a running season can be paused. This season is {}", phase))
+	}
+}
+
+/// End the season, and return the final standings.
+/// A season that has already ended is left as it is
+pub fn end(world: &mut World) -> String {
+	world.phase = Phase::Ended;
+	standings(world)
+}
+
+pub fn standings(world: &World) -> String {
+	let mut standings = scoring::leaderboard(&world.field, world.turn);
+	match &world.winner {
+		Some((winner, turn)) => standings.push_str(&format!("Winner: {} at turn {}\n", winner.0, turn)),
+		None => standings.push_str("The season ended without a winner\n")
+	}
+	standings
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		UserId,
+		commands::Order,
+		entity::Entity,
+		Pos
+	};
+
+	fn orders(user: &str, orders: &[&str]) -> (UserId, Vec<Order>) {
+		(UserId(user.to_string()), orders.iter().map(|s| Order::from_str(s).unwrap()).collect())
+	}
+
+	#[test]
+	fn test_season() {
+		let mut world = World::from_str("size:2,1; plot_size:10,10; phase:registration;;").unwrap();
+		let alice = UserId("alice".to_string());
+		let report = world.update(&[orders("alice", &["0,0 claim", "1,1 build stockpile"])]);
+		assert_eq!(report.messages(&alice), &["Command '1,1 build stockpile' failed: Only 'claim' is accepted during registration".to_string()]);
+		assert_eq!(world.field.get(Pos::new(5, 5)), Some(Entity::Capital(alice.clone())));
+		assert_eq!(world.turn, 0);
+		assert!(pause(&mut world).is_err());
+
+		start(&mut world).unwrap();
+		assert_eq!(world.phase, Phase::Running);
+		assert!(start(&mut world).is_err());
+		world.update(&[orders("alice", &["1,1 build stockpile"])]);
+		assert_eq!(world.turn, 1);
+
+		pause(&mut world).unwrap();
+		let mut world = World::from_str(&world.serialise()).unwrap();
+		assert_eq!(world.phase, Phase::Paused);
+		let report = world.update(&[orders("alice", &["1,1 remove"])]);
+		assert_eq!(report.messages(&alice), &["The season is paused. No commands are executed".to_string()]);
+		assert_eq!(world.turn, 1);
+		assert_eq!(world.field.get(Pos::new(1, 1)), Some(Entity::Stockpile(None)));
+
+		start(&mut world).unwrap();
+		let standings = end(&mut world);
+		assert!(standings.contains("   1     30      1         1          0      0  alice\n"));
+		assert!(standings.ends_with("The season ended without a winner\n"));
+		let ended = world.serialise();
+		assert_eq!(end(&mut world), standings);
+		assert_eq!(world.serialise(), ended);
+		assert!(start(&mut world).is_err());
+		let report = world.update(&[orders("alice", &["1,1 remove"])]);
+		assert_eq!(report.messages(&alice), &["The season has ended. No more commands are executed".to_string()]);
+
+		assert!(Phase::from_str("frozen").is_err());
+	}
+
+	#[test]
+	fn test_no_victory_during_registration() {
+		let mut world = World::from_str("size:2,1; plot_size:10,10; phase:registration; victory:plots 50; victory:score after 0;;").unwrap();
+		let report = world.update(&[orders("alice", &["0,0 claim"])]);
+		assert!(report.messages(&UserId("alice".to_string())).is_empty());
+		assert_eq!(world.winner, None);
+		assert_eq!(world.phase, Phase::Registration);
+
+		start(&mut world).unwrap();
+		world.update(&[]);
+		assert_eq!(world.winner, Some((UserId("alice".to_string()), 1)));
+		assert_eq!(world.phase, Phase::Ended);
+	}
+}
@@ -0,0 +1,302 @@
+//! A daemon that collects commands over a local Unix socket, for players that don't have a shell account.
+//!
+//! Every request is a single connection. The first line is the request, optionally followed by a body
+//! that ends with a line containing only a dot. Lines in the body that start with a dot get an extra dot.
+//! The response has the same form: "ok" or "error <message>", followed by a body.
+//!
+//! Player requests, authenticated with the token of the player:
+//! - `submit <token>` with the commands for the next turn as body
+//! - `standing <token>` with the standing orders as body
+//! - `feedback <token>` returns and clears the messages for the player
+//!
+//! Requests from the game itself, authenticated with the admin token:
+//! - `users <token>` lists all players
+//! - `take <token> <user>` returns and clears the commands of a player
+//! - `standing-orders <token> <user>` returns the standing orders of a player
+//! - `output <token> <user>` with a message for the player as body
+//!
+//! Everything is kept in memory only: submissions, standing orders and feedback are lost when the server restarts.
+
+use std::collections::HashMap;
+use std::io;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::{
+	UserId,
+	utils::{partition, constant_time_eq}
+};
+
+/// The largest body that is accepted, in bytes
+const MAX_BODY: usize = 64 * 1024;
+
+/// The longest request line that is accepted, in bytes
+const MAX_REQUEST: usize = 1024;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Default)]
+pub struct Server {
+	/// Player for each token
+	tokens: HashMap<String, UserId>,
+	admin_token: String,
+	submissions: HashMap<UserId, String>,
+	standing_orders: HashMap<UserId, String>,
+	feedback: HashMap<UserId, Vec<String>>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+	Ok(Vec<String>),
+	Error(String)
+}
+
+impl Server {
+
+	pub fn new(tokens: HashMap<String, UserId>, admin_token: String) -> Self {
+		Self {tokens, admin_token, ..Default::default()}
+	}
+
+	/// Read a tokens file with a line "<token> <user>" for every player
+	pub fn parse_tokens(text: &str) -> HashMap<String, UserId> {
+		text.lines()
+			.map(str::trim)
+			.filter(|line| !line.is_empty() && !line.starts_with('#'))
+			.map(partition)
+			.filter(|(_, user)| !user.is_empty())
+			.map(|(token, user)| (token.to_string(), UserId(user.to_string())))
+			.collect()
+	}
+
+	/// Handle every connection in its own thread, so a slow client does not block the others
+	pub fn serve(self, listener: UnixListener) -> io::Result<()> {
+		let server = Arc::new(Mutex::new(self));
+		for stream in listener.incoming() {
+			let server = Arc::clone(&server);
+			// A misbehaving client should not stop the server
+			let result = stream.and_then(|stream| thread::Builder::new().spawn(move || {
+				if let Err(err) = handle_connection(&server, stream) {
+					eprintln!("{}  connection error: {}", Utc::now(), err);
+				}
+			}));
+			if let Err(err) = result {
+				eprintln!("{}  connection error: {}", Utc::now(), err);
+			}
+		}
+		Ok(())
+	}
+
+	pub fn handle_request(&mut self, request: &str, body: Option<String>) -> Response {
+		let (kind, args) = partition(request);
+		let (token, user) = partition(args);
+		let user = UserId(user.to_string());
+		match kind {
+			"submit" | "standing" | "feedback" => {
+				let player = match self.tokens.get(token) {
+					Some(player) => player.clone(),
+					None => return Response::Error("Invalid token".to_string())
+				};
+				match kind {
+					"submit" => {
+						self.submissions.insert(player, body.unwrap_or_default());
+						Response::Ok(vec![])
+					}
+					"standing" => {
+						self.standing_orders.insert(player, body.unwrap_or_default());
+						Response::Ok(vec![])
+					}
+					_ => Response::Ok(self.feedback.remove(&player).unwrap_or_default())
+				}
+			}
+			"users" | "take" | "standing-orders" | "output" => {
+				if self.admin_token.is_empty() || !constant_time_eq(token, &self.admin_token) {
+					return Response::Error("Invalid token".to_string());
+				}
+				match kind {
+					"users" => {
+						let mut users: Vec<String> = self.tokens.values().map(|user| user.0.clone()).collect();
+						users.sort();
+						Response::Ok(users)
+					}
+					"take" => Response::Ok(self.submissions.remove(&user).map(|text| text.lines().map(String::from).collect()).unwrap_or_default()),
+					"standing-orders" => Response::Ok(self.standing_orders.get(&user).map(|text| text.lines().map(String::from).collect()).unwrap_or_default()),
+					_ => {
+						let message = body.unwrap_or_default();
+						self.feedback.entry(user).or_default().push(format!("{}  {}", Utc::now(), message));
+						Response::Ok(vec![])
+					}
+				}
+			}
+			_ => Response::Error(format!("Invalid request '{}'", kind))
+		}
+	}
+}
+
+/// Read the request and its body from the client without holding the lock, so only handling the request waits for other connections
+fn handle_connection(server: &Mutex<Server>, stream: UnixStream) -> io::Result<()> {
+	stream.set_read_timeout(Some(TIMEOUT))?;
+	stream.set_write_timeout(Some(TIMEOUT))?;
+	let mut reader = BufReader::new(stream.try_clone()?);
+	let request = match read_line_limited(&mut reader, MAX_REQUEST) {
+		Ok(request) => request,
+		Err(err) => return write_response(&stream, &Response::Error(err.to_string()))
+	};
+	let (kind, _) = partition(request.trim());
+	let body = if ["submit", "standing", "output"].contains(&kind) {
+		match read_body(&mut reader) {
+			Ok(body) => Some(body),
+			Err(err) => return write_response(&stream, &Response::Error(err.to_string()))
+		}
+	} else {
+		None
+	};
+	let response = server.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).handle_request(request.trim(), body);
+	write_response(&stream, &response)
+}
+
+/// Read a line of at most `limit` bytes, including the newline
+fn read_line_limited<R: BufRead>(reader: &mut R, limit: usize) -> io::Result<String> {
+	let mut line = String::new();
+	reader.take(limit as u64).read_line(&mut line)?;
+	if line.len() == limit && !line.ends_with('\n') {
+		return Err(io::Error::new(io::ErrorKind::InvalidData, "Line too long"));
+	}
+	Ok(line)
+}
+
+fn read_body<R: BufRead>(reader: &mut R) -> io::Result<String> {
+	let mut lines = Vec::new();
+	let mut size = 0;
+	loop {
+		let mut line = String::new();
+		// one byte more than fits, so a line that is too long is noticed below
+		if reader.take((MAX_BODY - size + 1) as u64).read_line(&mut line)? == 0 {
+			return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Body did not end with '.'"));
+		}
+		size += line.len();
+		if size > MAX_BODY {
+			return Err(io::Error::new(io::ErrorKind::InvalidData, "Body too large"));
+		}
+		let line = line.trim_end_matches(['\r', '\n']);
+		if line == "." {
+			return Ok(lines.join("\n"));
+		}
+		lines.push(line.strip_prefix('.').unwrap_or(line).to_string());
+	}
+}
+
+fn write_body<W: Write>(mut writer: W, lines: &[String]) -> io::Result<()> {
+	for line in lines {
+		if line.starts_with('.') {
+			write!(writer, ".")?;
+		}
+		writeln!(writer, "{}", line)?;
+	}
+	writeln!(writer, ".")
+}
+
+fn write_response(stream: &UnixStream, response: &Response) -> io::Result<()> {
+	match response {
+		Response::Ok(lines) => {
+			writeln!(&*stream, "ok")?;
+			write_body(stream, lines)
+		}
+		Response::Error(msg) => {
+			writeln!(&*stream, "error {}", msg)?;
+			write_body(stream, &[])
+		}
+	}
+}
+
+/// Send a request to the server and return the body of the response
+pub fn request(socket: &Path, request: &str, body: Option<&str>) -> io::Result<Vec<String>> {
+	let stream = UnixStream::connect(socket)?;
+	stream.set_read_timeout(Some(TIMEOUT))?;
+	writeln!(&stream, "{}", request)?;
+	if let Some(body) = body {
+		write_body(&stream, &body.lines().map(String::from).collect::<Vec<String>>())?;
+	}
+	let mut reader = BufReader::new(stream);
+	let mut status = String::new();
+	reader.read_line(&mut status)?;
+	let lines: Vec<String> = read_body(&mut reader)?.lines().map(String::from).collect();
+	let (kind, msg) = partition(status.trim());
+	match kind {
+		"ok" => Ok(lines),
+		"error" => Err(io::Error::other(msg)),
+		_ => Err(io::Error::new(io::ErrorKind::InvalidData, format!("Invalid response '{}'", status.trim())))
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn server() -> Server {
+		Server::new(Server::parse_tokens("# players\nabc alice\n\ndef bob the builder\n"), "secret".to_string())
+	}
+
+	fn lines(text: &[&str]) -> Response {
+		Response::Ok(text.iter().map(|s| s.to_string()).collect())
+	}
+
+	#[test]
+	fn test_requests() {
+		let mut server = server();
+		assert_eq!(server.handle_request("users secret", None), lines(&["alice", "bob the builder"]));
+		assert_eq!(server.handle_request("users abc", None), Response::Error("Invalid token".to_string()));
+		assert_eq!(server.handle_request("users secreT", None), Response::Error("Invalid token".to_string()));
+		assert_eq!(server.handle_request("users secret2", None), Response::Error("Invalid token".to_string()));
+		assert_eq!(server.handle_request("submit xyz", Some("1,1 use".to_string())), Response::Error("Invalid token".to_string()));
+		assert_eq!(server.handle_request("submit abc", Some("1,1 use\n2,2 use".to_string())), lines(&[]));
+		assert_eq!(server.handle_request("standing def", Some("every turn: 3,3 use".to_string())), lines(&[]));
+		assert_eq!(server.handle_request("take secret alice", None), lines(&["1,1 use", "2,2 use"]));
+		assert_eq!(server.handle_request("take secret alice", None), lines(&[]));
+		assert_eq!(server.handle_request("standing-orders secret bob the builder", None), lines(&["every turn: 3,3 use"]));
+		assert_eq!(server.handle_request("standing-orders secret bob the builder", None), lines(&["every turn: 3,3 use"]));
+		assert_eq!(server.handle_request("output secret alice", Some("hello".to_string())), lines(&[]));
+		match server.handle_request("feedback abc", None) {
+			Response::Ok(feedback) => {
+				assert_eq!(feedback.len(), 1);
+				assert!(feedback[0].ends_with("  hello"));
+			}
+			err => panic!("{:?}", err)
+		}
+		assert_eq!(server.handle_request("feedback abc", None), lines(&[]));
+		assert_eq!(server.handle_request("shutdown secret", None), Response::Error("Invalid request 'shutdown'".to_string()));
+	}
+
+	#[test]
+	fn test_body() {
+		let text: Vec<String> = vec![".hidden".to_string(), "1,1 use".to_string(), ".".to_string()];
+		let mut buffer = Vec::new();
+		write_body(&mut buffer, &text).unwrap();
+		assert_eq!(String::from_utf8(buffer.clone()).unwrap(), "..hidden\n1,1 use\n..\n.\n");
+		assert_eq!(read_body(&mut buffer.as_slice()).unwrap(), ".hidden\n1,1 use\n.");
+		assert!(read_body(&mut "1,1 use\n".as_bytes()).is_err());
+		assert_eq!(read_body(&mut "x".repeat(MAX_BODY + 10).as_bytes()).unwrap_err().to_string(), "Body too large");
+		assert_eq!(read_line_limited(&mut "users secret\nmore".as_bytes(), MAX_REQUEST).unwrap(), "users secret\n");
+		assert!(read_line_limited(&mut "x".repeat(MAX_REQUEST).as_bytes(), MAX_REQUEST).is_err());
+	}
+
+	#[test]
+	fn test_socket() {
+		let path = std::env::temp_dir().join(format!("evilcadastre-test-{}.sock", std::process::id()));
+		let _ = std::fs::remove_file(&path);
+		let listener = UnixListener::bind(&path).unwrap();
+		std::thread::spawn(move || server().serve(listener));
+		// a client that does not send anything does not block the others
+		let _silent = UnixStream::connect(&path).unwrap();
+		assert_eq!(request(&path, "submit abc", Some("1,1 use")).unwrap(), Vec::<String>::new());
+		assert_eq!(request(&path, "take secret alice", None).unwrap(), vec!["1,1 use".to_string()]);
+		assert_eq!(request(&path, "take wrong alice", None).unwrap_err().to_string(), "Invalid token");
+		let _ = std::fs::remove_file(&path);
+	}
+}
@@ -0,0 +1,146 @@
+
+use std::collections::HashSet;
+
+use crate::{
+	world::World,
+	field::Field,
+	entity::Entity,
+	commands::{Command, Action, Order},
+	bot::{self, Bot},
+	scoring,
+	season::Phase,
+	trade
+};
+
+pub const CSV_HEADER: &str = "turn,player,plots,produced,trained,destroyed";
+
+/// What a player did in a single update
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Statistics {
+	/// Resources produced by woodcutters, quarries and farms
+	pub produced: usize,
+	/// Units trained
+	pub trained: usize,
+	/// Buildings of others destroyed by attacks
+	pub destroyed: usize
+}
+
+/// What the commands of a player did in an update, from the field before and after it and the messages of the player.
+/// Commands that are reported as failed did nothing
+fn turn_statistics(before: &Field, after: &Field, commands: &[Command], messages: &[String]) -> Statistics {
+	let mut failures: Vec<&String> = messages.iter().collect();
+	let mut stats = Statistics::default();
+	let mut destroyed = HashSet::new();
+	for command in commands {
+		let failed = format!("Command '{}' failed: ", command);
+		if let Some(index) = failures.iter().position(|message| message.starts_with(&failed)) {
+			failures.remove(index);
+			continue;
+		}
+		let Some(pos) = command.pos.resolve(before.plot_size) else {
+			continue;
+		};
+		match (&command.action, before.get(pos)) {
+			(Action::Use, Some(Entity::Woodcutter | Entity::Quarry | Entity::Farm)) => stats.produced += 1,
+			(Action::Use, Some(Entity::Lair | Entity::Barracks)) => stats.trained += 1,
+			(Action::Attack(dir), Some(_)) => destroyed.extend(before.neighbour_lane(pos, dir.clone()).into_iter().filter(|p| {
+				before.get(*p).is_some_and(|ent| !ent.properties().mortal) && after.get(*p).is_none()
+			})),
+			_ => {}
+		}
+	}
+	stats.destroyed = destroyed.len();
+	stats
+}
+
+/// Quote a CSV field when it contains a separator or a quote
+fn csv_field(s: &str) -> String {
+	if s.contains([',', '"']) {
+		format!("\"{}\"", s.replace('"', "\"\""))
+	} else {
+		s.to_string()
+	}
+}
+
+/// Let the bots play against each other for `turns` turns, or until the game ends.
+/// Returns one line of statistics for every bot in every turn, as CSV without the header
+pub fn simulate(world: &mut World, bots: &[Bot], turns: u64) -> Vec<String> {
+	let mut rows = Vec::new();
+	let takeover_cost = trade::total(&world.takeover_cost);
+	for _ in 0..turns {
+		if world.phase == Phase::Ended {
+			break;
+		}
+		let commands: Vec<Vec<Command>> = bots.iter().map(|bot| bot::commands(&world.field, &bot.user, bot.strategy, &takeover_cost)).collect();
+		let orders: Vec<_> = bots.iter().zip(&commands).map(|(bot, commands)| {
+			(bot.user.clone(), commands.iter().cloned().map(Order::Command).collect())
+		}).collect();
+		let before = world.field.clone();
+		let report = world.update(&orders);
+		let scores = scoring::scores(&world.field);
+		for (bot, commands) in bots.iter().zip(&commands) {
+			let plots = scores.iter().find(|(user, _)| user == &bot.user).map(|(_, score)| score.plots).unwrap_or(0);
+			let stats = turn_statistics(&before, &world.field, commands, &report.messages(&bot.user));
+			rows.push(format!("{},{},{},{},{},{}", world.turn, csv_field(&bot.user.0), plots, stats.produced, stats.trained, stats.destroyed));
+		}
+	}
+	rows
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::str::FromStr;
+	use crate::{Pos, UserId};
+
+	#[test]
+	fn test_simulate() {
+		let bots = vec![Bot::from_str("alice:economic").unwrap(), Bot::from_str("bob:raider").unwrap()];
+		let mut world = World::init(Pos::new(10, 10), Pos::new(3, 3), 7);
+		let rows = simulate(&mut world, &bots, 5);
+		assert_eq!(rows.len(), 10);
+		assert!(rows[0].starts_with("1,alice,1,0,0,0"));
+		assert!(rows[9].starts_with("5,bob,1,"));
+		assert!(rows.iter().any(|row| !row.ends_with(",0,0,0")));
+
+		// The same seed gives the same game
+		let mut again = World::init(Pos::new(10, 10), Pos::new(3, 3), 7);
+		assert_eq!(simulate(&mut again, &bots, 5), rows);
+		assert_ne!(World::init(Pos::new(10, 10), Pos::new(3, 3), 8).field, World::init(Pos::new(10, 10), Pos::new(3, 3), 7).field);
+
+		let mut ended = World::new(Field::from_str("size:1,1; plot_size:10,10;; 5,5 capital:alice;").unwrap());
+		ended.phase = Phase::Ended;
+		assert!(simulate(&mut ended, &bots, 5).is_empty());
+		assert_eq!(ended.field.get(Pos::new(5, 5)), Some(Entity::Capital(UserId("alice".to_string()))));
+	}
+
+	#[test]
+	fn test_turn_statistics() {
+		let mut world = World::from_str(
+			"size:2,1; plot_size:10,10;;
+			5,5 capital:alice; 8,8 woodcutter; 9,8 forest; 6,6 stockpile; 7,5 lair;
+			15,4 capital:bob; 13,8 raider; 12,4 farm;"
+		).unwrap();
+		let alice = vec![Command::from_str("8,8 use").unwrap(), Command::from_str("7,5 use").unwrap(), Command::from_str("8,8 use").unwrap()];
+		let bob = vec![Command::from_str("13,8 attack west").unwrap()];
+		let before = world.field.clone();
+		let report = world.update(&[
+			(UserId("alice".to_string()), alice.iter().cloned().map(Order::Command).collect()),
+			(UserId("bob".to_string()), bob.iter().cloned().map(Order::Command).collect())
+		]);
+		let stats = |user: &str, commands| turn_statistics(&before, &world.field, commands, &report.messages(&UserId(user.to_string())));
+		assert_eq!(stats("alice", &alice), Statistics{produced: 1, trained: 0, destroyed: 0});
+		assert_eq!(stats("bob", &bob), Statistics{produced: 0, trained: 0, destroyed: 1});
+	}
+
+	#[test]
+	fn test_csv_field() {
+		assert_eq!(csv_field("alice"), "alice");
+		assert_eq!(csv_field("bob, the 2nd"), "\"bob, the 2nd\"");
+		assert_eq!(csv_field("\"eve\""), "\"\"\"eve\"\"\"");
+		let bots = vec![Bot::from_str("bob, the 2nd:raider").unwrap()];
+		let rows = simulate(&mut World::init(Pos::new(10, 10), Pos::new(3, 3), 7), &bots, 1);
+		assert!(rows[0].starts_with("1,\"bob, the 2nd\",1,"));
+	}
+}
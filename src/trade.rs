@@ -0,0 +1,131 @@
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{
+	UserId,
+	commands::Coord,
+	resources::{Resource, ResourceCount},
+	errors::ParseError,
+	parse_err,
+	utils::partition_by
+};
+
+/// An amount of a single resource, written as "3 wood"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Amount {
+	pub count: usize,
+	pub resource: Resource
+}
+
+impl FromStr for Amount {
+	type Err = ParseError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (count, resource) = partition_by(s.trim(), " ");
+		let count = count.parse().map_err(|_| parse_err!("Invalid amount '{}'", count).with_word(count))?;
+		if count == 0 {
+			return Err(parse_err!("Can not trade nothing").with_word(s.trim()));
+		}
+		Ok(Self{count, resource: Resource::from_str(resource.trim())?})
+	}
+}
+
+impl fmt::Display for Amount {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{} {}", self.count, self.resource)
+	}
+}
+
+/// Parse a list of amounts separated by commas, like "10 wood, 5 food"
+pub fn parse_amounts(s: &str) -> Result<Vec<Amount>, ParseError> {
+	s.split(',').map(str::trim).filter(|part| !part.is_empty()).map(Amount::from_str).collect()
+}
+
+pub fn format_amounts(amounts: &[Amount]) -> String {
+	amounts.iter().map(Amount::to_string).collect::<Vec<String>>().join(", ")
+}
+
+pub fn total(amounts: &[Amount]) -> ResourceCount {
+	let mut total = ResourceCount::default();
+	for amount in amounts {
+		for _ in 0..amount.count {
+			total.add_resource(amount.resource);
+		}
+	}
+	total
+}
+
+/// The terms of a trade as written in an offer ("offer 3 wood for 2 stone to alice via 19,5")
+/// or in the matching acceptance ("accept 3 wood for 2 stone from bob via 20,5").
+/// In both the first amount is what the offering player gives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trade {
+	pub give: Amount,
+	pub take: Amount,
+	/// The other side of the trade
+	pub partner: UserId,
+	/// The tradepost of the player that writes the order
+	pub via: Coord
+}
+
+impl Trade {
+
+	/// Parse the part after "offer" or "accept", where `direction` is "to" or "from"
+	pub fn parse(s: &str, direction: &str) -> Result<Self, ParseError> {
+		let expected = || parse_err!("Expected '<amount> for <amount> {} <player> via <x,y>'. Found '{}'", direction, s.trim());
+		let (terms, via) = s.rsplit_once(" via ").ok_or_else(expected)?;
+		let (amounts, partner) = terms.split_once(&format!(" {} ", direction)).ok_or_else(expected)?;
+		let (give, take) = amounts.split_once(" for ").ok_or_else(expected)?;
+		let partner = partner.trim();
+		if partner.is_empty() {
+			return Err(expected());
+		}
+		UserId::validate(partner)?;
+		Ok(Self{
+			give: Amount::from_str(give)?,
+			take: Amount::from_str(take)?,
+			partner: UserId(partner.to_string()),
+			via: Coord::from_str(via.trim())?
+		})
+	}
+
+	/// Whether `accept`, written by `accepter`, accepts this offer of `offerer`
+	pub fn matches(&self, offerer: &UserId, accept: &Trade, accepter: &UserId) -> bool {
+		&self.partner == accepter && &accept.partner == offerer && self.give == accept.give && self.take == accept.take
+	}
+
+	pub fn describe(&self, direction: &str) -> String {
+		format!("{} for {} {} {} via {}", self.give, self.take, direction, self.partner.0, self.via)
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::Pos;
+
+	#[test]
+	fn test_trade_parsing() {
+		let offer = Trade::parse("3 wood for 2 stone to alice the great via 19,5", "to").unwrap();
+		assert_eq!(offer, Trade{
+			give: Amount{count: 3, resource: Resource::Wood},
+			take: Amount{count: 2, resource: Resource::Stone},
+			partner: UserId("alice the great".to_string()),
+			via: Pos::new(19, 5).into()
+		});
+		assert_eq!(offer.describe("to"), "3 wood for 2 stone to alice the great via 19,5");
+		let accept = Trade::parse("3 wood for 2 stone from bob via @2,0 0,5", "from").unwrap();
+		let alice = UserId("alice the great".to_string());
+		let bob = UserId("bob".to_string());
+		assert!(offer.matches(&bob, &accept, &alice));
+		assert!(!offer.matches(&alice, &accept, &bob));
+		let other = Trade::parse("3 wood for 1 stone from bob via 20,5", "from").unwrap();
+		assert!(!offer.matches(&bob, &other, &alice));
+		assert_eq!(Trade::parse("3 wood for 2 stone via 19,5", "to").map_err(|e| e.msg), Err("Expected '<amount> for <amount> to <player> via <x,y>'. Found '3 wood for 2 stone via 19,5'".to_string()));
+		assert_eq!(Trade::parse("0 wood for 2 stone to alice via 19,5", "to").map_err(|e| e.msg), Err("Can not trade nothing".to_string()));
+		assert_eq!(Trade::parse("many wood for 2 stone to alice via 19,5", "to").map_err(|e| e.msg), Err("Invalid amount 'many'".to_string()));
+		assert!(Trade::parse("3 gold for 2 stone to alice via 19,5", "to").is_err());
+	}
+}
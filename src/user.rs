@@ -1,3 +1,272 @@
 
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::NaiveDate;
+
+use crate::{
+	errors::ParseError,
+	parse_err,
+	render::Colour,
+	utils::{partition_by, percent_encode, percent_decode}
+};
+
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub struct UserId(pub String);
+
+/// The reserved owner of the plots that are controlled by the engine
+pub const BARBARIANS: &str = "barbarians";
+
+impl UserId {
+
+	pub fn barbarians() -> Self {
+		Self(BARBARIANS.to_string())
+	}
+
+	pub fn is_barbarians(&self) -> bool {
+		self.0 == BARBARIANS
+	}
+
+	/// Check that the name can be used for a player.
+	/// Separators are escaped in the world file, but control characters would still mess up logs and terminals
+	pub fn validate(name: &str) -> Result<(), ParseError> {
+		if name.is_empty() {
+			return Err(parse_err!("A player name can not be empty"));
+		}
+		if let Some(c) = name.chars().find(|c| c.is_control()) {
+			return Err(parse_err!("Player name '{}' contains invalid character {:?}", name, c));
+		}
+		if name == BARBARIANS {
+			return Err(parse_err!("The player name '{}' is reserved", name));
+		}
+		Ok(())
+	}
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Player {
+	pub id: UserId,
+	pub display_name: Option<String>,
+	pub colour: Option<Colour>,
+	pub joined: NaiveDate,
+	/// Other names that refer to this player, such as the name before a rename
+	pub aliases: Vec<UserId>
+}
+
+impl Player {
+
+	pub fn validate_display_name(name: &str) -> Result<(), ParseError> {
+		if name.contains(';') || name.contains(char::is_control) {
+			return Err(parse_err!("Display name '{}' can not contain ';' or control characters", name));
+		}
+		Ok(())
+	}
+}
+
+/// All registered players, stored with one line per player:
+/// "<name>; joined:<yyyy-mm-dd>; display_name:<name>; colour:#rrggbb; aliases:<name>,<name>"
+/// The names are escaped in the same way as in the world file
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct PlayerRegistry {
+	players: Vec<Player>
+}
+
+impl PlayerRegistry {
+
+	pub fn players(&self) -> &[Player] {
+		&self.players
+	}
+
+	pub fn get(&self, user: &UserId) -> Option<&Player> {
+		self.players.iter().find(|player| &player.id == user)
+	}
+
+	/// The player that is known by this name or alias
+	pub fn resolve(&self, name: &str) -> Option<&UserId> {
+		self.players.iter()
+			.find(|player| player.id.0 == name || player.aliases.iter().any(|alias| alias.0 == name))
+			.map(|player| &player.id)
+	}
+
+	pub fn display_name(&self, user: &UserId) -> String {
+		self.get(user)
+			.and_then(|player| player.display_name.clone())
+			.unwrap_or_else(|| user.0.clone())
+	}
+
+	pub fn colour(&self, user: &UserId) -> Option<Colour> {
+		self.get(user).and_then(|player| player.colour)
+	}
+
+	pub fn register(&mut self, id: UserId, joined: NaiveDate) -> Result<&mut Player, ParseError> {
+		UserId::validate(&id.0)?;
+		if self.resolve(&id.0).is_some() {
+			return Err(parse_err!("Player name '{}' is already taken", id.0));
+		}
+		self.players.push(Player{id, display_name: None, colour: None, joined, aliases: Vec::new()});
+		Ok(self.players.last_mut().unwrap())
+	}
+
+	/// Let `alias` refer to the player too. An alias can only refer to one player
+	pub fn add_alias(&mut self, user: &UserId, alias: UserId) -> Result<(), ParseError> {
+		UserId::validate(&alias.0)?;
+		if self.resolve(&alias.0).is_some_and(|other| other != user) {
+			return Err(parse_err!("Player name '{}' is already taken", alias.0));
+		}
+		let player = self.players.iter_mut()
+			.find(|player| &player.id == user)
+			.ok_or(parse_err!("Unknown player '{}'", user.0))?;
+		if player.id != alias && !player.aliases.contains(&alias) {
+			player.aliases.push(alias);
+		}
+		Ok(())
+	}
+
+	/// Give a player a new name. The old name stays as an alias so commands under that name still arrive
+	pub fn rename(&mut self, old: &UserId, new: UserId) -> Result<(), ParseError> {
+		UserId::validate(&new.0)?;
+		if self.resolve(&new.0).is_some_and(|other| other != old) {
+			return Err(parse_err!("Player name '{}' is already taken", new.0));
+		}
+		let player = self.players.iter_mut()
+			.find(|player| &player.id == old)
+			.ok_or(parse_err!("Unknown player '{}'", old.0))?;
+		player.aliases.retain(|alias| alias != &new);
+		player.aliases.push(old.clone());
+		player.id = new;
+		Ok(())
+	}
+}
+
+impl FromStr for PlayerRegistry {
+	type Err = ParseError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let mut registry = Self::default();
+		for line in s.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+			let mut items = line.split(';');
+			let id = decode_name(items.next().unwrap().trim());
+			let mut joined = None;
+			let mut display_name = None;
+			let mut colour = None;
+			let mut aliases = Vec::new();
+			for item in items {
+				let (name, arg) = partition_by(item, ":");
+				let arg = arg.trim();
+				match name.trim() {
+					"joined" => {
+						joined = Some(NaiveDate::parse_from_str(arg, "%Y-%m-%d")
+							.map_err(|e| parse_err!("Invalid join date '{}' for player '{}': {}", arg, id.0, e))?);
+					}
+					"display_name" => {
+						Player::validate_display_name(arg)?;
+						display_name = Some(arg.to_string());
+					}
+					"colour" => {colour = Some(Colour::from_str(arg)?)}
+					"aliases" => {
+						aliases = arg.split(',').map(str::trim).filter(|a| !a.is_empty()).map(decode_name).collect();
+					}
+					"" => {}
+					other => {return Err(parse_err!("Invalid player property '{}'", other))}
+				}
+			}
+			let joined = joined.ok_or(parse_err!("No join date for player '{}'", id.0))?;
+			let player = registry.register(id.clone(), joined)?;
+			player.display_name = display_name;
+			player.colour = colour;
+			for alias in aliases {
+				registry.add_alias(&id, alias)?;
+			}
+		}
+		Ok(registry)
+	}
+}
+
+impl fmt::Display for PlayerRegistry {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for player in self.players.iter() {
+			write!(f, "{}; joined:{}", percent_encode(&player.id.0), player.joined.format("%Y-%m-%d"))?;
+			if let Some(display_name) = &player.display_name {
+				write!(f, "; display_name:{}", display_name)?;
+			}
+			if let Some(colour) = &player.colour {
+				write!(f, "; colour:{}", colour.hex())?;
+			}
+			if !player.aliases.is_empty() {
+				let aliases: Vec<String> = player.aliases.iter().map(|alias| percent_encode(&alias.0)).collect();
+				write!(f, "; aliases:{}", aliases.join(","))?;
+			}
+			writeln!(f)?;
+		}
+		Ok(())
+	}
+}
+
+fn decode_name(s: &str) -> UserId {
+	UserId(percent_decode(s))
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn user(name: &str) -> UserId {
+		UserId(name.to_string())
+	}
+
+	#[test]
+	fn test_validate() {
+		assert!(UserId::validate("alice").is_ok());
+		assert!(UserId::validate("Ælfrida_2").is_ok());
+		assert!(UserId::validate("al;i:ce #2").is_ok());
+		for name in &["", "al\nice", "al\u{1b}ice", BARBARIANS] {
+			assert!(UserId::validate(name).is_err(), "{:?}", name);
+		}
+	}
+
+	#[test]
+	fn test_registry() {
+		let text = "
+			# players
+			alice; joined:2026-10-01; display_name:Alice the Evil; colour:#ff8800
+			bob; joined:2026-10-02; aliases:bobby,robert
+		";
+		let mut registry = PlayerRegistry::from_str(text).unwrap();
+		assert_eq!(registry.players().len(), 2);
+		assert_eq!(registry.display_name(&user("alice")), "Alice the Evil");
+		assert_eq!(registry.display_name(&user("bob")), "bob");
+		assert_eq!(registry.colour(&user("alice")), Some(Colour::new(0xff, 0x88, 0x00)));
+		assert_eq!(registry.resolve("robert"), Some(&user("bob")));
+		assert_eq!(registry.resolve("carol"), None);
+		assert_eq!(PlayerRegistry::from_str(&registry.to_string()).unwrap(), registry);
+
+		let date = NaiveDate::from_ymd_opt(2026, 10, 3).unwrap();
+		assert!(registry.register(user("bobby"), date).is_err());
+		assert!(registry.register(user("car\nol"), date).is_err());
+		registry.register(user("carol"), date).unwrap();
+		assert!(registry.rename(&user("alice"), user("bob")).is_err());
+		assert!(registry.rename(&user("alice"), user("")).is_err());
+		registry.rename(&user("alice"), user("alicia")).unwrap();
+		assert_eq!(registry.resolve("alice"), Some(&user("alicia")));
+		assert_eq!(registry.display_name(&user("alicia")), "Alice the Evil");
+		assert_eq!(
+			registry.to_string(),
+			"alicia; joined:2026-10-01; display_name:Alice the Evil; colour:#ff8800; aliases:alice\n\
+			bob; joined:2026-10-02; aliases:bobby,robert\n\
+			carol; joined:2026-10-03\n"
+		);
+		registry.rename(&user("carol"), user("carol; the #1")).unwrap();
+		assert!(registry.to_string().contains("carol%3B%20the%20%231; joined:2026-10-03; aliases:carol\n"));
+		assert_eq!(PlayerRegistry::from_str(&registry.to_string()).unwrap(), registry);
+		assert!(PlayerRegistry::from_str("dave; joined:yesterday").is_err());
+		assert!(PlayerRegistry::from_str("dave; colour:#ff8800").is_err());
+		assert!(PlayerRegistry::from_str("dave; joined:2026-10-01; aliases:d\nerin; joined:2026-10-01; aliases:d").is_err());
+
+		assert!(registry.add_alias(&user("carol; the #1"), user("robert")).is_err());
+		assert!(registry.add_alias(&user("carol; the #1"), user("bob")).is_err());
+		registry.add_alias(&user("carol; the #1"), user("caz")).unwrap();
+		registry.add_alias(&user("carol; the #1"), user("caz")).unwrap();
+		assert_eq!(registry.get(&user("carol; the #1")).unwrap().aliases, vec![user("carol"), user("caz")]);
+	}
+}
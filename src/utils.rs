@@ -1,19 +1,18 @@
 
 
-pub fn partition(s: &str) -> (String, String) {
-	let mut parts: Vec<String> = s.splitn(2, ' ').map(String::from).collect();
-	while parts.len() < 2 {
-		parts.push("".to_string())
-	}
-	(parts.remove(0), parts.remove(0))
+/// Split at the first space. Both parts are slices of `s`, so parse errors can point at them
+pub fn partition(s: &str) -> (&str, &str) {
+	partition_by(s, " ")
 }
 
-pub fn partition_by(s: &str, pat: &str) -> (String, String) {
-	let mut parts: Vec<String> = s.splitn(2, pat).map(String::from).collect();
-	while parts.len() < 2 {
-		parts.push("".to_string())
-	}
-	(parts.remove(0), parts.remove(0))
+pub fn partition_by<'a>(s: &'a str, pat: &str) -> (&'a str, &'a str) {
+	s.split_once(pat).unwrap_or((s, &s[s.len()..]))
+}
+
+/// Where `part`, which must be a slice of `s`, starts in `s`
+pub fn offset_in(s: &str, part: &str) -> Option<usize> {
+	let offset = (part.as_ptr() as usize).checked_sub(s.as_ptr() as usize)?;
+	(offset + part.len() <= s.len()).then_some(offset)
 }
 
 pub fn truncated<T: Clone>(a: &[T], l: usize) -> Vec<T> {
@@ -24,16 +23,106 @@ pub fn truncated<T: Clone>(a: &[T], l: usize) -> Vec<T> {
 	c
 }
 
+/// Compare two secrets in a time that does not depend on where they differ
+pub fn constant_time_eq(a: &str, b: &str) -> bool {
+	a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
 pub fn randomize (mut seed: u32) -> u32 {
-	seed += 92857;
+	seed = seed.wrapping_add(92857);
 	seed ^= seed << 13;
 	seed ^= seed >> 17;
 	seed ^= seed << 5;
 	seed
 }
 
+/// Characters that separate the parts of the world file, and the escape character itself
+const SEPARATORS: &[char] = &['%', ';', ':', ',', '#'];
+
+/// Whether `percent_encode` escapes a character
+fn is_escaped(c: char) -> bool {
+	SEPARATORS.contains(&c) || c.is_whitespace() || c.is_control()
+}
+
+/// Percent-encode the characters that would otherwise be read as separators or whitespace
+pub fn percent_encode(s: &str) -> String {
+	let mut encoded = String::with_capacity(s.len());
+	for c in s.chars() {
+		if is_escaped(c) {
+			let mut buffer = [0; 4];
+			for byte in c.encode_utf8(&mut buffer).bytes() {
+				encoded.push_str(&format!("%{:02X}", byte));
+			}
+		} else {
+			encoded.push(c);
+		}
+	}
+	encoded
+}
+
+/// The inverse of `percent_encode`.
+/// Only escapes that `percent_encode` writes are decoded, so names from before escaping keep any other `%`
+pub fn percent_decode(s: &str) -> String {
+	let mut decoded = String::with_capacity(s.len());
+	let mut rest = s;
+	while let Some(c) = rest.chars().next() {
+		let (c, len) = decode_escape(rest).unwrap_or((c, c.len_utf8()));
+		decoded.push(c);
+		rest = &rest[len..];
+	}
+	decoded
+}
+
+/// The character that is escaped at the start of `s` and the length of the escape, if `percent_encode` would write it like that
+fn decode_escape(s: &str) -> Option<(char, usize)> {
+	let byte = |i: usize| {
+		let hex = s.get(i * 3..i * 3 + 3)?.strip_prefix('%')?;
+		if !hex.bytes().all(|b| b.is_ascii_digit() || (b'A'..=b'F').contains(&b)) {
+			return None;
+		}
+		u8::from_str_radix(hex, 16).ok()
+	};
+	let len = match byte(0)?.leading_ones() {
+		0 => 1,
+		n @ 2..=4 => n as usize,
+		_ => return None
+	};
+	let bytes = (0..len).map(byte).collect::<Option<Vec<u8>>>()?;
+	let c = std::str::from_utf8(&bytes).ok()?.chars().next()?;
+	is_escaped(c).then_some((c, len * 3))
+}
 
 #[allow(dead_code)]
 pub fn identity<T>(t: T) -> T {
 	t
 }
+
+/// Levenshtein distance between two strings
+pub fn edit_distance(a: &str, b: &str) -> usize {
+	let b: Vec<char> = b.chars().collect();
+	let mut row: Vec<usize> = (0..=b.len()).collect();
+	for (i, ca) in a.chars().enumerate() {
+		let mut diagonal = row[0];
+		row[0] = i + 1;
+		for (j, cb) in b.iter().enumerate() {
+			let substitution = diagonal + if ca == *cb {0} else {1};
+			diagonal = row[j + 1];
+			row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+		}
+	}
+	row[b.len()]
+}
+
+/// The candidate that is closest to the word, if it is close enough to be a likely typo
+pub fn closest<S: AsRef<str>>(word: &str, candidates: &[S]) -> Option<String> {
+	let word = word.to_lowercase();
+	if word.is_empty() {
+		return None;
+	}
+	let max_distance = (word.chars().count() / 3).max(1);
+	candidates.iter()
+		.map(|candidate| (edit_distance(&word, candidate.as_ref()), candidate.as_ref()))
+		.filter(|(distance, _)| *distance <= max_distance)
+		.min_by_key(|(distance, _)| *distance)
+		.map(|(_, candidate)| candidate.to_string())
+}
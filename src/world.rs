@@ -1,26 +1,59 @@
 
 use std::collections::{HashSet};
+use std::str::FromStr;
 
 use crate::{
-	field::Field,
-	commands::{Command, Action},
+	field::{self, Field},
+	commands::{Command, Action, Order},
 	UserId,
 	entity::Entity,
 	resources::{Resource, ResourceCount},
 	buildings::BuildingType,
 	rules,
-	utils,
+	utils::{self, percent_encode, percent_decode},
+	report::Report,
+	errors::{CommandError, ParseError},
+	command_err,
+	parse_err,
+	diplomacy::{Alliances, Declaration},
+	trade::{self, Trade, Amount},
+	scoring::{self, VictoryCondition},
+	season::Phase,
+	activity::Activity,
+	respawn::{self, RespawnPolicy},
+	barbarians,
 	Pos
 };
 
 pub struct World {
-	pub field: Field
+	pub field: Field,
+	/// The number of updates so far
+	pub turn: u64,
+	pub alliances: Alliances,
+	/// The game ends when any of these is reached
+	pub victory: Vec<VictoryCondition>,
+	/// The winner and the turn the game ended
+	pub winner: Option<(UserId, u64)>,
+	pub phase: Phase,
+	pub activity: Activity,
+	/// The number of turns without successful commands after which the plots of a player fall into ruin
+	pub decay_after: Option<u64>,
+	pub respawn: Option<RespawnPolicy>,
+	/// The players that lost their capital and did not claim again yet, with the turn they were eliminated
+	pub eliminated: Vec<(UserId, u64)>,
+	/// The last turn each player relocated their capital
+	pub relocations: Vec<(UserId, u64)>,
+	/// The cost of taking over a plot with a scoutpost
+	pub takeover_cost: Vec<Amount>,
+	/// The food every unit eats each turn
+	pub upkeep: Option<usize>
 }
 
 
 impl World {
 	
-	pub fn init(plot_size: Pos, size: Pos) -> World {
+	/// Generate a new world. Different seeds give different forests and swamps
+	pub fn init(plot_size: Pos, size: Pos, seed: u32) -> World {
 		let mut field = Field::new(plot_size, size);
 		for keep in field.list_keeps() {
 			let plot = keep / plot_size;
@@ -32,7 +65,7 @@ impl World {
 			field.set_tile(plot_start + Pos::new(plot_size.x-1, plot_size.y-1), Entity::Rock);
 			// Place some random forests and swamps
 			let tiles: Vec<Pos> = field.find_all(keep, None);
-			let r0 = utils::randomize((plot.x + plot.y * 67679) as u32);
+			let r0 = utils::randomize((plot.x + plot.y * 67679) as u32 ^ seed);
 			let r1 = utils::randomize(r0);
 			let r2 = utils::randomize(r1);
 			field.set_tile(tiles[(r0 as usize) % tiles.len()], Entity::Forest);
@@ -43,17 +76,17 @@ impl World {
 	}
 	
 	pub fn new(field: Field) -> World{
-		Self{field}
+		Self{field, turn: 0, alliances: Alliances::default(), victory: Vec::new(), winner: None, phase: Phase::Running, activity: Activity::default(), decay_after: None, respawn: None, eliminated: Vec::new(), relocations: Vec::new(), takeover_cost: rules::default_takeover_cost(), upkeep: None}
 	}
 	
-	fn order_commands(commands: &[(UserId, Vec<Command>)]) -> Vec<Vec<(UserId, Command)>> {
+	fn order_commands<T: Clone>(commands: &[(UserId, Vec<T>)]) -> Vec<Vec<(UserId, T)>> {
 		let mut command_iterators = Vec::new();
 		for (user, comms) in commands {
 			command_iterators.push((user.clone(), comms.iter()));
 		}
 		let mut ordered_commands = Vec::new();
 		loop {
-			let heads: Vec<(UserId, Command)> = command_iterators
+			let heads: Vec<(UserId, T)> = command_iterators
 				.iter_mut()
 				.filter_map(|(user, it)| Some((user.clone(), it.next()?)))
 				.map(|(user, command)| (user, command.clone()))
@@ -66,93 +99,364 @@ impl World {
 		ordered_commands
 	}
 	
-	pub fn update(&mut self, commands: &[(UserId, Vec<Command>)]){
+	pub fn update(&mut self, orders: &[(UserId, Vec<Order>)]) -> Report {
+		self.update_with_standing_orders(orders, &[])
+	}
+	
+	/// Standing orders are executed every turn, filling up the action limit after the normal orders of a player
+	pub fn update_with_standing_orders(&mut self, orders: &[(UserId, Vec<Order>)], standing_orders: &[(UserId, Vec<Order>)]) -> Report {
+		let mut report = Report::default();
+		if let Some((winner, turn)) = &self.winner {
+			report.broadcast(format!("The game is over: {} won at turn {}. No more commands are executed", winner.0, turn));
+			return report;
+		}
+		match self.phase {
+			Phase::Paused => {
+				report.broadcast("The season is paused. No commands are executed".to_string());
+				return report;
+			}
+			Phase::Ended => {
+				report.broadcast("The season has ended. No more commands are executed".to_string());
+				return report;
+			}
+			Phase::Registration | Phase::Running => {}
+		}
+		// During registration only claims are executed, and the turn does not count
+		let registration = self.phase == Phase::Registration;
+		// The barbarians go through the same path as the players
+		let mut orders = orders.to_vec();
+		if !registration {
+			orders.push((UserId::barbarians(), barbarians::orders(&self.field)));
+		}
+		let orders = &orders[..];
 		let mut used_tiles = HashSet::new();
-		let ordered = Self::order_commands(&commands.iter().map(|(user, commands)| {
-			(user.clone(), utils::truncated(commands, 10))
-		}).collect::<Vec<(UserId, Vec<Command>)>>());
+		let capitals_before = scoring::capital_owners(&self.field);
+		if !registration {
+			self.turn += 1;
+			if let Some(food) = self.upkeep {
+				self.feed_units(food, &mut report);
+			}
+			let all_orders: Vec<(&UserId, &Order)> = orders.iter()
+				.chain(standing_orders.iter())
+				.flat_map(|(u, o)| o.iter().map(move |order| (u, order)))
+				.collect();
+			for (user, order) in all_orders.iter() {
+				self.run_diplomacy(user, order, &mut report);
+			}
+			// Trades are free: they don't count towards the action limit below
+			self.run_trades(&all_orders, &mut used_tiles, &mut report);
+			for (a, b) in self.alliances.expire(self.turn) {
+				report.add(&a, format!("Your alliance with {} has ended", b.0));
+				report.add(&b, format!("Your alliance with {} has ended", a.0));
+			}
+		}
+		// Orders are expanded before ordering so the action limit applies to the resulting commands
+		// Each command is marked with whether it is a standing order
+		let mut commands: Vec<(UserId, Vec<(Command, bool)>)> = Vec::new();
+		for (user, user_orders, standing) in orders.iter().map(|(u, o)| (u, o, false)).chain(standing_orders.iter().map(|(u, o)| (u, o, true))) {
+			let expanded = user_orders.iter().flat_map(|order| order.expand(&self.field)).map(|command| (command, standing));
+			match commands.iter_mut().find(|(u, _)| u == user) {
+				Some((_, user_commands)) => user_commands.extend(expanded),
+				None => commands.push((user.clone(), expanded.collect()))
+			}
+		}
+		let ordered = Self::order_commands(&commands.into_iter().map(|(user, user_commands)| {
+			(user, utils::truncated(&user_commands, 10))
+		}).collect::<Vec<(UserId, Vec<(Command, bool)>)>>());
 		for command_round in ordered {
 			let mut destroyed = Vec::new();
-			for (user, command) in command_round {
-				self.run_command(&user, &command, &mut used_tiles, &mut destroyed);
+			for (user, (command, standing)) in command_round {
+				let result = if registration && command.action != Action::Claim {
+					Err(command_err!("Only 'claim' is accepted during registration"))
+				} else {
+					self.run_command(&user, &command, &mut used_tiles, &mut destroyed)
+				};
+				if let (Ok(()), Action::Relocate(target)) = (&result, &command.action) {
+					report.add(&user, format!("Your capital moved from {} to {}", command.pos, target));
+				}
+				// Command files are not cleared, so only commands that still do something show that the player is around
+				if result.is_ok() && !standing {
+					self.activity.record(&user, self.turn);
+				}
+				if let Err(err) = result {
+					let kind = if standing {"Standing order"} else {"Command"};
+					report.add(&user, format!("{} '{}' failed: {}", kind, command, err));
+				}
 			}
 			for tile in destroyed {
 				self.field.clear_tile(tile);
 			}
 		}
+		let capitals_after = scoring::capital_owners(&self.field);
+		for user in capitals_before.iter().filter(|user| !capitals_after.contains(user)) {
+			report.broadcast(format!("{} was eliminated at turn {}", user.0, self.turn));
+			if let Some(policy) = &self.respawn {
+				report.add(user, format!("You can claim a new plot from turn {}", policy.respawn_turn(self.turn)));
+			}
+			self.eliminated.push((user.clone(), self.turn));
+		}
+		// Nobody can win before play has started
+		if !registration {
+			self.decay(&mut report);
+			if let Some((winner, reason)) = self.victory.iter().find_map(|condition| condition.winner(&self.field, self.turn, capitals_before.len())) {
+				report.broadcast(format!("{} has won the game by {}", winner.0, reason));
+				self.winner = Some((winner, self.turn));
+				self.phase = Phase::Ended;
+			}
+		}
+		report
+	}
+	
+	/// Let every plot pay for its units. The barbarians live off the land
+	fn feed_units(&mut self, food: usize, report: &mut Report) {
+		for keep in self.field.list_keeps() {
+			let owner = match self.field.plot_owner(keep) {
+				Some(owner) if !owner.is_barbarians() => owner,
+				_ => continue
+			};
+			let deserted = rules::upkeep(&mut self.field, keep, food);
+			if deserted > 0 {
+				report.add(&owner, format!("{} units in the plot of {} deserted because there was not enough food", deserted, keep));
+			}
+		}
+	}
+	
+	/// Abandon the plots of players that have been inactive for too long, and let ruins crumble
+	fn decay(&mut self, report: &mut Report) {
+		self.activity.track(&self.field, self.turn);
+		if let Some(limit) = self.decay_after {
+			for user in self.activity.idle(self.turn, limit) {
+				let plots = rules::abandon(&mut self.field, &user);
+				self.activity.forget(&user);
+				if plots > 0 {
+					report.broadcast(format!("{} has been inactive for {} turns. Their {} plots fell into ruin", user.0, limit, plots));
+				}
+			}
+		}
+		rules::crumble(&mut self.field);
+	}
+	
+	fn run_diplomacy(&mut self, user: &UserId, order: &Order, report: &mut Report) {
+		match order {
+			Order::Ally(other) => match self.alliances.declare(user, other) {
+				Ok(Declaration::Proposed) => {
+					report.add(other, format!("{} wants to form an alliance with you. Write 'ally {}' to accept", user.0, user.0));
+				}
+				Ok(Declaration::Formed) => {
+					report.add(user, format!("You are now allied with {}", other.0));
+					report.add(other, format!("You are now allied with {}", user.0));
+				}
+				Ok(Declaration::Existing) => {}
+				Err(err) => report.add(user, format!("Alliance with {} failed: {}", other.0, err))
+			}
+			Order::LeaveAlliance(other) => match self.alliances.leave(user, other, self.turn) {
+				Ok(Some(ends)) => {
+					report.add(user, format!("Your alliance with {} ends at turn {}", other.0, ends));
+					report.add(other, format!("{} is leaving your alliance, which ends at turn {}", user.0, ends));
+				}
+				Ok(None) => report.add(user, format!("You withdrew your alliance proposal to {}", other.0)),
+				Err(err) => report.add(user, format!("Leaving alliance with {} failed: {}", other.0, err))
+			}
+			Order::Say{to: Some(recipient), text} => {
+				report.add(recipient, format!("Turn {}, message from {}: {}", self.turn, user.0, text));
+				report.add(user, format!("Turn {}, you said to {}: {}", self.turn, recipient.0, text));
+			}
+			Order::Say{to: None, text} => {
+				report.broadcast(format!("Turn {}, message from {} to everyone: {}", self.turn, user.0, text));
+			}
+			Order::Command(_) | Order::ForEach{..} | Order::Offer(_) | Order::Accept(_) => {}
+		}
+	}
+	
+	/// Execute the offers that were accepted in the same turn, and tell the players about the others
+	fn run_trades(&mut self, orders: &[(&UserId, &Order)], used_tiles: &mut HashSet<Pos>, report: &mut Report) {
+		let mut offers: Vec<(&UserId, &Trade)> = orders.iter().filter_map(|(user, order)| match order {
+			Order::Offer(trade) => Some((*user, trade)),
+			_ => None
+		}).collect();
+		for (accepter, accept) in orders.iter().filter_map(|(user, order)| match order {
+			Order::Accept(trade) => Some((*user, trade)),
+			_ => None
+		}) {
+			let index = match offers.iter().position(|(offerer, offer)| offer.matches(offerer, accept, accepter)) {
+				Some(index) => index,
+				None => {
+					report.add(accepter, format!("Trade 'accept {}' failed: There is no matching offer from {}", accept.describe("from"), accept.partner.0));
+					continue;
+				}
+			};
+			let (offerer, offer) = offers.remove(index);
+			match self.run_trade(offerer, offer, accepter, accept, used_tiles) {
+				Ok(()) => {
+					self.activity.record(offerer, self.turn);
+					self.activity.record(accepter, self.turn);
+					report.add(offerer, format!("Trade with {} done: you gave {} and got {}", accepter.0, offer.give, offer.take));
+					report.add(accepter, format!("Trade with {} done: you gave {} and got {}", offerer.0, offer.take, offer.give));
+				}
+				Err(err) => {
+					report.add(offerer, format!("Trade 'offer {}' failed: {}", offer.describe("to"), err));
+					report.add(accepter, format!("Trade 'accept {}' failed: {}", accept.describe("from"), err));
+				}
+			}
+		}
+		for (offerer, offer) in offers {
+			report.add(offerer, format!("Trade 'offer {}' was not accepted", offer.describe("to")));
+			let tradepost = offer.via.resolve(self.field.plot_size).and_then(|pos| self.field.across_border(pos));
+			report.add(&offer.partner, format!(
+				"{} offers {} for {}. Write 'accept {} for {} from {} via {}' to accept it in the same turn",
+				offerer.0, offer.give, offer.take, offer.give, offer.take, offerer.0,
+				tradepost.map(|pos| pos.to_string()).unwrap_or_else(|| "<tradepost>".to_string())
+			));
+		}
 	}
 	
-	pub fn run_command(&mut self, user: &UserId, command: &Command, used_tiles: &mut HashSet<Pos>, destroyed: &mut Vec<Pos>) {
+	fn run_trade(&mut self, offerer: &UserId, offer: &Trade, accepter: &UserId, accept: &Trade, used_tiles: &mut HashSet<Pos>) -> Result<(), CommandError> {
+		if offerer == accepter {
+			return Err(command_err!("You can not trade with yourself"));
+		}
+		let plot_size = self.field.plot_size;
+		let offer_pos = offer.via.resolve(plot_size).ok_or_else(|| command_err!("Position is outside its plot"))?;
+		let accept_pos = accept.via.resolve(plot_size).ok_or_else(|| command_err!("Position is outside its plot"))?;
+		for (user, pos) in [(offerer, offer_pos), (accepter, accept_pos)] {
+			if self.field.plot_owner(pos).as_ref() != Some(user) {
+				return Err(command_err!("{} does not own the plot of {}", user.0, pos));
+			}
+			if self.field.get(pos) != Some(Entity::Tradepost) {
+				return Err(command_err!("There is no tradepost at {}", pos));
+			}
+			if used_tiles.contains(&pos) {
+				return Err(command_err!("Tile {} was already used this turn", pos));
+			}
+		}
+		if self.field.across_border(offer_pos) != Some(accept_pos) || self.field.across_border(accept_pos) != Some(offer_pos) {
+			return Err(command_err!("The tradeposts at {} and {} are not next to each other", offer_pos, accept_pos));
+		}
+		// Each side pays from its own plot, and the stockpiles that are paid from can receive the other resources
+		for (user, pos, pays, receives) in [(offerer, offer_pos, offer.give, offer.take), (accepter, accept_pos, offer.take, offer.give)] {
+			if rules::available_resources(&self.field, pos).resources.get(&pays.resource).copied().unwrap_or(0) < pays.count {
+				return Err(command_err!("{} does not have {}", user.0, pays));
+			}
+			if rules::free_storage(&self.field, pos) + pays.count < receives.count {
+				return Err(command_err!("{} does not have enough empty stockpiles", user.0));
+			}
+		}
+		// Both sides are done on a copy, so a trade is never half executed
+		let mut field = self.field.clone();
+		for (user, pos, pays) in [(offerer, offer_pos, offer.give), (accepter, accept_pos, offer.take)] {
+			if !rules::pay(&mut field, pos, &ResourceCount::from_vec(&vec![pays.resource; pays.count])) {
+				return Err(command_err!("{} does not have {}", user.0, pays));
+			}
+		}
+		for (user, pos, receives) in [(offerer, offer_pos, offer.take), (accepter, accept_pos, offer.give)] {
+			for _ in 0..receives.count {
+				rules::add_resource(&mut field, pos, receives.resource).ok_or_else(|| command_err!("{} does not have room for {}", user.0, receives))?;
+			}
+		}
+		self.field = field;
+		used_tiles.insert(offer_pos);
+		used_tiles.insert(accept_pos);
+		Ok(())
+	}
+	
+	pub fn run_command(&mut self, user: &UserId, command: &Command, used_tiles: &mut HashSet<Pos>, destroyed: &mut Vec<Pos>) -> Result<(), CommandError> {
+		
+		let pos = command.pos.resolve(self.field.plot_size).ok_or_else(|| command_err!("Position is outside its plot"))?;
+		
+		if let Some(condition) = &command.condition {
+			if !condition.holds(&self.field, user) {
+				return Err(command_err!("Condition '{}' does not hold", condition));
+			}
+		}
 		
-		if used_tiles.contains(&command.pos){
-			return;
+		if used_tiles.contains(&pos){
+			return Err(command_err!("Tile {} was already used this turn", pos));
 		}
 		
-		if command.action == Action::Claim && !self.field.list_keeps().iter().any(|p| self.field.get(*p) == Some(Entity::Capital(user.clone()))) {
-			rules::claim_first_keep(&mut self.field, command.pos, user.clone());
+		if command.action == Action::Claim {
+			if self.field.list_keeps().iter().any(|p| self.field.get(*p) == Some(Entity::Capital(user.clone()))) {
+				return Err(command_err!("You already have a capital"));
+			}
+			let eliminated = self.eliminated.iter().find(|(u, _)| u == user).map(|(_, turn)| *turn);
+			let policy = self.respawn.as_ref().filter(|_| eliminated.is_some());
+			if let (Some(turn), Some(policy)) = (eliminated, policy) {
+				if self.turn < policy.respawn_turn(turn) {
+					return Err(command_err!("You can claim a new plot from turn {}", policy.respawn_turn(turn)));
+				}
+			}
+			let keep = match policy.filter(|policy| policy.spawn) {
+				Some(_) => {
+					let keep = respawn::spawn_plot(&self.field).ok_or_else(|| command_err!("There is no free plot to respawn in"))?;
+					rules::claim_first_keep(&mut self.field, keep, user.clone()).ok_or_else(|| command_err!("There is no free plot to respawn in"))?
+				}
+				None => rules::claim_first_keep(&mut self.field, pos, user.clone()).ok_or_else(|| command_err!("This plot can not be claimed"))?
+			};
+			if let Some(policy) = policy {
+				respawn::place_bundle(&mut self.field, keep, &policy.bundle);
+			}
+			self.eliminated.retain(|(u, _)| u != user);
+			used_tiles.insert(keep);
+			return Ok(());
 		}
 		
-		if self.field.plot_owner(command.pos).as_ref() != Some(user) {
-			return
+		if self.field.plot_owner(pos).as_ref() != Some(user) {
+			return Err(command_err!("You do not own the plot of {}", pos));
 		}
 		
-		used_tiles.insert(command.pos);
+		used_tiles.insert(pos);
 		
-		match (command.action.clone(), self.field.get(command.pos)) {
+		match (command.action.clone(), self.field.get(pos)) {
 			(Action::Build(building), None) => {
 				if (
 						building == BuildingType::Road ||
 						building == BuildingType::Tradepost ||
 						building == BuildingType::Scoutpost
-						) && self.field.across_border(command.pos) == None {
-					return;
+						) && self.field.across_border(pos).is_none() {
+					return Err(command_err!("A {} must be built on the border of a plot", building));
 				}
-				if building == BuildingType::Woodcutter && !self.field.neighbours(command.pos, Some(Entity::Forest)){
-					return;
+				if building == BuildingType::Woodcutter && !self.field.neighbours(pos, Some(Entity::Forest)){
+					return Err(command_err!("A woodcutter must be built next to a forest"));
 				}
-				if building == BuildingType::Quarry && !self.field.neighbours(command.pos, Some(Entity::Rock)){
-					return;
+				if building == BuildingType::Quarry && !self.field.neighbours(pos, Some(Entity::Rock)){
+					return Err(command_err!("A quarry must be built next to a rock"));
 				}
 				let (cost, ent) = building.cost_result();
-				if rules::pay(&mut self.field, command.pos, &cost){
-					self.field.set_tile(command.pos, ent);
+				if !rules::pay(&mut self.field, pos, &cost){
+					return Err(command_err!("Not enough resources"));
 				}
+				self.field.set_tile(pos, ent);
 			}
 			
 			(Action::Move(target), Some(ent)) => {
+				let target = target.resolve(self.field.plot_size).ok_or_else(|| command_err!("Target is outside its plot"))?;
 				if used_tiles.contains(&target) {
-					return;
+					return Err(command_err!("Tile {} was already used this turn", target));
 				}
 				match ent {
 					e if e.properties().movable => {
-						if let Some(pos) = rules::move_unit_destination(&self.field, command.pos, target) {
-							self.field.switch_tiles(command.pos, pos);
-							used_tiles.insert(pos);
-							used_tiles.insert(target);
-						}
-					}
-					Entity::Stockpile(Some(_res)) => {
-						if let Some(pos) = rules::move_resource_destination(&self.field, command.pos, target) {
-							self.field.switch_tiles(command.pos, pos);
-							used_tiles.insert(pos);
-							used_tiles.insert(target);
-						}
+						let destination = rules::move_unit_destination(&self.field, &self.alliances, pos, target).ok_or_else(|| command_err!("Can not move to {}", target))?;
+						self.field.switch_tiles(pos, destination);
+						used_tiles.insert(destination);
+						used_tiles.insert(target);
 					}
-					Entity::Capital(owner) if &owner == user => {
-						if self.field.get(target) == Some(Entity::Keep(user.clone())) {
-							self.field.switch_tiles(command.pos, target);
-							used_tiles.insert(target);
-						}
+					e if !e.stored_resources().is_empty() => {
+						let destination = rules::move_resource_destination(&self.field, &self.alliances, pos, target).ok_or_else(|| command_err!("Can not move to {}", target))?;
+						rules::move_resource(&mut self.field, pos, destination);
+						used_tiles.insert(destination);
+						used_tiles.insert(target);
 					}
-					_ => {}
+					ent => {return Err(command_err!("A {} can not move", ent))}
 				}
 			}
 			
 			(Action::Attack(dir), Some(ent)) => {
 			
-				let lane = self.field.neighbour_lane(command.pos, dir);
-				if lane.is_empty() || self.field.plot_owner(lane[0]).as_ref() == Some(user){
-					return;
+				let lane = self.field.neighbour_lane(pos, dir);
+				let target_owner = lane.first().and_then(|p| self.field.plot_owner(*p));
+				if lane.is_empty() || target_owner.as_ref() == Some(user){
+					return Err(command_err!("There is no hostile plot in that direction"));
+				}
+				if target_owner.is_some_and(|owner| self.alliances.allied(user, &owner)) {
+					return Err(command_err!("You can not attack an ally"));
 				}
 				match ent {
 					Entity::Raider => {
@@ -192,68 +496,943 @@ impl World {
 							}
 						}
 					}
-					_ => {}
+					ent => {return Err(command_err!("A {} can not attack", ent))}
 				}
 			}
 			
 			(Action::Use, Some(ent)) => {
 				match ent {
 					Entity::Woodcutter => {
-						rules::add_resource(&mut self.field, command.pos, Resource::Wood);
+						rules::add_resource(&mut self.field, pos, Resource::Wood).ok_or_else(|| command_err!("No room to store the resource"))?;
 					}
 					Entity::Quarry => {
-						rules::add_resource(&mut self.field, command.pos, Resource::Wood);
+						rules::add_resource(&mut self.field, pos, Resource::Wood).ok_or_else(|| command_err!("No room to store the resource"))?;
 					}
 					Entity::Farm => {
-						rules::add_resource(&mut self.field, command.pos, Resource::Food);
+						rules::add_resource(&mut self.field, pos, Resource::Food).ok_or_else(|| command_err!("No room to store the resource"))?;
 					}
 					Entity::Lair => {
-						if rules::pay(&mut self.field, command.pos, &ResourceCount::from_vec(&[Resource::Food, Resource::Food, Resource::Food])) {
-							if let Some(pos) = self.field.change_tile(command.pos, None, Some(Entity::Raider)) {
-								used_tiles.insert(pos);
-							}
+						if !rules::pay(&mut self.field, pos, &rules::training_cost(&Entity::Lair).unwrap()) {
+							return Err(command_err!("Not enough resources"));
+						}
+						if let Some(unit_pos) = self.field.change_tile(pos, None, Some(Entity::Raider)) {
+							used_tiles.insert(unit_pos);
 						}
 					}
 					Entity::Barracks => {
-						if rules::pay(&mut self.field, command.pos, &ResourceCount::from_vec(&[Resource::Food, Resource::Food, Resource::Food, Resource::Food, Resource::Food, Resource::Wood, Resource::Stone])) {
-							// todo: will require iron later
-							if let Some(pos) = self.field.change_tile(command.pos, None, Some(Entity::Warrior)) {
-								used_tiles.insert(pos);
-							}
+						if !rules::pay(&mut self.field, pos, &rules::training_cost(&Entity::Barracks).unwrap()) {
+							return Err(command_err!("Not enough resources"));
+						}
+						if let Some(unit_pos) = self.field.change_tile(pos, None, Some(Entity::Warrior)) {
+							used_tiles.insert(unit_pos);
 						}
 					}
 					Entity::Scoutpost => {
-						if let Some(pos) = self.field.across_border(command.pos) {
-							if self.field.plot_owner(command.pos) == self.field.plot_owner(pos) {
-								return;
-							}
-							if self.field.tiles_in_plot(pos).into_iter().filter_map(|p| self.field.get(p)).any(|ent| ent.properties().defender) {
-								return;
-							}
-							if rules::pay(&mut self.field, command.pos, &ResourceCount::from_vec(&[
-									Resource::Wood, Resource::Wood, Resource::Wood, Resource::Wood, Resource::Wood, Resource::Wood, Resource::Wood, Resource::Wood, Resource::Wood, Resource::Wood,
-									Resource::Food, Resource::Food, Resource::Food, Resource::Food, Resource::Food,
-									Resource::Stone, Resource::Stone, Resource::Stone, Resource::Stone, Resource::Stone])) {
-								let keep = self.field.keep_location(pos);
-								rules::destroy_keep(&mut self.field, keep);
-								self.field.set_tile(self.field.keep_location(pos), Entity::Keep(user.clone()));
-							}
+						let target = self.field.across_border(pos).ok_or_else(|| command_err!("Scoutpost is not on a border"))?;
+						if self.field.plot_owner(pos) == self.field.plot_owner(target) {
+							return Err(command_err!("You already own the plot of {}", target));
 						}
+						if self.field.plot_owner(target).is_some_and(|owner| self.alliances.allied(user, &owner)) {
+							return Err(command_err!("You can not take over the plot of an ally"));
+						}
+						if self.field.tiles_in_plot(target).into_iter().filter_map(|p| self.field.get(p)).any(|ent| ent.properties().defender) {
+							return Err(command_err!("The plot of {} is defended", target));
+						}
+						if !rules::pay(&mut self.field, pos, &trade::total(&self.takeover_cost)) {
+							return Err(command_err!("Not enough resources"));
+						}
+						let keep = self.field.keep_location(target);
+						// The loot of the barbarians stays in the plot, and their other plots are not connected to a capital
+						if !self.field.plot_owner(keep).is_some_and(|owner| owner.is_barbarians()) {
+							rules::destroy_keep(&mut self.field, keep);
+						}
+						self.field.set_tile(self.field.keep_location(target), Entity::Keep(user.clone()));
 					}
-					_ => {}
+					ent => {return Err(command_err!("A {} can not be used", ent))}
 				}
 			}
 			
+			(Action::Relocate(target), Some(Entity::Capital(_))) => {
+				let target = target.resolve(self.field.plot_size).ok_or_else(|| command_err!("Target is outside its plot"))?;
+				if self.field.get(target) != Some(Entity::Keep(user.clone())) {
+					return Err(command_err!("A capital can only be relocated to one of your keeps"));
+				}
+				if used_tiles.contains(&target) {
+					return Err(command_err!("Tile {} was already used this turn", target));
+				}
+				if let Some((_, last)) = self.relocations.iter().find(|(u, _)| u == user) {
+					if self.turn < last + rules::RELOCATION_COOLDOWN {
+						return Err(command_err!("Your capital can not be relocated again before turn {}", last + rules::RELOCATION_COOLDOWN));
+					}
+				}
+				if !rules::pay(&mut self.field, pos, &rules::relocation_cost()) {
+					return Err(command_err!("Not enough resources"));
+				}
+				self.field.switch_tiles(pos, target);
+				used_tiles.insert(target);
+				self.relocations.retain(|(u, _)| u != user);
+				self.relocations.push((user.clone(), self.turn));
+			}
+			(Action::Relocate(_), Some(ent)) => {return Err(command_err!("A {} can not be relocated", ent))}
 			(Action::Remove, Some(ent)) if ent.properties().removable => {
-				self.field.clear_tile(command.pos);
+				self.field.clear_tile(pos);
+			}
+			(Action::Build(_), Some(ent)) => {return Err(command_err!("Tile {} is not empty: {}", pos, ent))}
+			(Action::Remove, Some(ent)) => {return Err(command_err!("A {} can not be removed", ent))}
+			(_, None) => {return Err(command_err!("Tile {} is empty", pos))}
+			(Action::Claim, Some(_)) => {}
+		}
+		Ok(())
+	}
+	
+	/// Give everything of a player to the player with the new name
+	pub fn rename_player(&mut self, old: &UserId, new: &UserId) {
+		self.field.rename_owner(old, new);
+		self.alliances.rename(old, new);
+		self.activity.rename(old, new);
+		for (user, _) in self.eliminated.iter_mut().chain(self.relocations.iter_mut()) {
+			if user == old {
+				*user = new.clone();
 			}
-			_ => {}
 		}
 	}
 	
 	pub fn serialise(&self) -> String {
-		self.field.to_string()
+		let mut meta = vec![format!("turn:{}", self.turn), format!("phase:{}", self.phase)];
+		meta.extend(self.victory.iter().map(|condition| format!("victory:{}", condition)));
+		if let Some((winner, turn)) = &self.winner {
+			meta.push(format!("winner:{},{}", percent_encode(&winner.0), turn));
+		}
+		if let Some(turns) = self.decay_after {
+			meta.push(format!("decay_after:{}", turns));
+		}
+		if let Some(policy) = &self.respawn {
+			meta.push(format!("respawn:{}", policy));
+		}
+		if let Some(food) = self.upkeep {
+			meta.push(format!("upkeep:{}", food));
+		}
+		if self.takeover_cost != rules::default_takeover_cost() {
+			meta.push(format!("takeover_cost:{}", trade::format_amounts(&self.takeover_cost)));
+		}
+		meta.extend(self.eliminated.iter().map(|(user, turn)| format!("eliminated:{},{}", percent_encode(&user.0), turn)));
+		meta.extend(self.relocations.iter().map(|(user, turn)| format!("relocated:{},{}", percent_encode(&user.0), turn)));
+		meta.extend(self.alliances.to_meta());
+		meta.extend(self.activity.to_meta());
+		self.field.serialise_with_meta(&meta)
 	}
 	
 }
 
+impl FromStr for World {
+	type Err = ParseError;
+	
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let mut world = Self::new(Field::from_str(s)?);
+		for (name, arg) in field::parse_meta(s) {
+			match name.as_str() {
+				"turn" => {world.turn = arg.parse().map_err(|_| parse_err!("Invalid turn '{}'", arg))?}
+				"phase" => world.phase = Phase::from_str(&arg)?,
+				"victory" => world.victory.push(VictoryCondition::from_str(&arg)?),
+				"winner" => {
+					let (winner, turn) = arg.rsplit_once(',').ok_or_else(|| parse_err!("Invalid winner '{}'", arg))?;
+					let winner = percent_decode(winner);
+					let turn = turn.parse().map_err(|_| parse_err!("Invalid turn '{}'", turn))?;
+					world.winner = Some((UserId(winner), turn));
+				}
+				"respawn" => world.respawn = Some(RespawnPolicy::from_str(&arg)?),
+				"upkeep" => {world.upkeep = Some(arg.parse().map_err(|_| parse_err!("Invalid upkeep '{}'", arg))?)}
+				"takeover_cost" => world.takeover_cost = trade::parse_amounts(&arg)?,
+				"eliminated" => world.eliminated.push(parse_user_turn(&name, &arg)?),
+				"relocated" => world.relocations.push(parse_user_turn(&name, &arg)?),
+				"decay_after" => {world.decay_after = Some(arg.parse().map_err(|_| parse_err!("Invalid number of turns '{}'", arg))?)}
+				_ => {
+					if !world.alliances.parse_meta(&name, &arg)? {
+						world.activity.parse_meta(&name, &arg)?;
+					}
+				}
+			}
+		}
+		Ok(world)
+	}
+}
+
+/// A meta item in the form "<player>,<turn>"
+fn parse_user_turn(name: &str, arg: &str) -> Result<(UserId, u64), ParseError> {
+	let (user, turn) = arg.rsplit_once(',').ok_or_else(|| parse_err!("Invalid {} '{}'", name, arg))?;
+	let user = percent_decode(user);
+	let turn = turn.parse().map_err(|_| parse_err!("Invalid turn '{}'", turn))?;
+	Ok((UserId(user), turn))
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	macro_rules! tileis {
+			($world: expr, $x: expr, $y: expr, $val: expr) => {assert_eq!($world.field.get(Pos::new($x, $y)), $val)}
+	}
+	
+	fn parse_commands(u: &str, c: &[&str]) -> (UserId, Vec<Order>) {
+		(UserId(u.to_string()), c.iter().map(|s| Order::from_str(s).unwrap()).collect())
+	}
+	
+	#[test]
+	fn test_simple_commands() {
+		let mut world = World::new(Field::from_str("size:5,5; plot_size:10,10 ;;").unwrap());
+		let (user, commands) = parse_commands("user", &[
+			"2,1 build stockpile",
+			"15,2 build woodcutter",
+			"6,2 build woodcutter",
+			"6,3 build woodcutter",
+			"0,0 claim",
+			"11,1 claim",
+			"11,2 build stockpile",
+			"6,2 build stockpile",
+			"8,0 build stockpile",
+			"8,1 build stockpile",
+			"8,2 build stockpile",
+			"8,3 build stockpile",
+			"8,4 build stockpile",
+			"8,5 build stockpile"
+		]);
+		world.update(&[(user.clone(), commands)]);
+		assert_eq!(world.field.plot_owner(Pos::new(0,0)), Some(user.clone()));
+		assert_eq!(world.field.plot_owner(Pos::new(9,9)), Some(user.clone()));
+		assert_eq!(world.field.plot_owner(Pos::new(11,11)), None);
+		assert_eq!(world.field.plot_owner(Pos::new(1,11)), None);
+		assert_eq!(world.field.plot_owner(Pos::new(11,1)), None);
+		tileis!(world, 2,1, None);//Some(Entity::Stockpile(None)));
+		tileis!(world, 15,2, None);
+		tileis!(world, 6,2, Some(Entity::Stockpile(None)));
+		tileis!(world, 6,3, None);
+		tileis!(world, 11,2, None);
+		tileis!(world, 8,0, Some(Entity::Stockpile(None)));
+		tileis!(world, 8,1, Some(Entity::Stockpile(None)));
+		tileis!(world, 8,2, None);
+		assert_eq!(world.field, Field::from_str(
+			"size:5,5; plot_size:10,10 ;;
+			5,5 capital:user;
+			6,2 stockpile;
+			8,0 stockpile;
+			8,1 stockpile;"
+		).unwrap());
+	}
+	
+	#[test]
+	fn test_woodcutting(){
+		let mut world = World::new(Field::from_str(
+			"size:5,5; plot_size:10,10 ;;
+			5,5 keep:user;
+			0,5 woodcutter;
+			1,5 stockpile;
+			2,5 stockpile;
+			0,2 stockpile;
+			9,5 woodcutter;
+			10,5 stockpile;"
+		).unwrap());
+		let (user, commands) = parse_commands("user", &[
+			"0,5 use",
+			"9,5 use"
+		]);
+		world.update(&[(user, commands)]);
+		
+		assert_eq!(world.field, Field::from_str(
+			"size:5,5; plot_size:10,10 ;;
+			5,5 keep:user;
+			0,5 woodcutter;
+			1,5 stockpile:wood;
+			2,5 stockpile:wood;
+			0,2 stockpile;
+			9,5 woodcutter;
+			10,5 stockpile;"
+		).unwrap());
+	}
+	
+	#[test]
+	fn test_attack(){
+		let mut world = World::new(Field::from_str(
+			"size:5,5; plot_size:10,10 ;;
+			5,5 keep:user;
+			6,6 lair;
+			1,9 raider;
+			3,3 woodcutter;
+			3,7 raider;
+			
+			15,4 keep:user;
+			11,6 raider;
+			
+			4,15 keep:other;
+			1,13 farm;
+			3,17 raider;
+			3,16 farm;"
+		).unwrap());
+		world.update(&[
+			parse_commands("user", &[
+				"1,9 attack south",
+				"11,6 attack west"
+			]),
+			parse_commands("other", &[
+				"3,17 attack north",
+			])
+		]);
+		
+		assert_eq!(world.field, Field::from_str(
+			"size:5,5; plot_size:10,10 ;;
+			5,5 keep:user;
+			6,6 lair;
+			1,9 raider;
+			3,3 woodcutter;
+			3,7 raider;
+			
+			15,4 keep:user;
+			11,6 raider;
+			
+			4,15 keep:other;
+			3,17 raider;
+			3,16 farm;"
+		).unwrap());
+	}
+	
+	
+	#[test]
+	fn test_move(){
+		let mut world = World::new(Field::from_str(
+			"size:5,5; plot_size:10,10 ;;
+			5,5 keep:user;
+			1,1 raider;
+			1,2 raider;
+			1,3 raider;
+			1,4 raider;
+			1,5 raider;
+			1,6 raider;
+			1,7 raider;
+			1,8 raider;
+			1,9 raider;
+			2,1 raider;
+			7,7 stockpile;
+			9,9 road;
+			6,6 road;
+			2,9 road;
+			9,2 road;
+			0,1 road;
+			1,0 road;
+			
+			
+			15,4 keep:user;
+			11,6 raider;
+			
+			4,15 keep:other;
+			1,13 farm;
+			3,17 raider;
+			3,16 farm;"
+		).unwrap());
+		world.update(&[
+			parse_commands("user", &[
+				"1,1 move 0,0",
+				"1,2 move 0,0",
+				"1,3 move 7,7",
+				"1,4 move 5,5",
+				
+				"1,5 move 6,6",
+				"1,6 move 9,9",
+				"1,7 move 9,2",
+				"1,8 move 2,9",
+				"1,0 move 1,0",
+				"2,1 move 19,9",
+			]),
+		]);
+		assert_eq!(world.field, Field::from_str(
+			"size:5,5; plot_size:10,10 ;;
+			5,5 keep:user;
+			0,0 raider;
+			1,2 raider;
+			1,3 raider;
+			1,4 raider;
+			1,5 raider;
+			1,6 raider;
+			10,2 raider;
+			1,8 raider;
+			1,9 raider;
+			2,1 raider;
+			7,7 stockpile;
+			9,9 road;
+			6,6 road;
+			2,9 road;
+			9,2 road;
+			0,1 road;
+			1,0 road;
+			
+			
+			15,4 keep:user;
+			11,6 raider;
+			
+			4,15 keep:other;
+			1,13 farm;
+			3,17 raider;
+			3,16 farm;"
+		).unwrap());
+	}
+	
+	#[test]
+	fn test_plot_local(){
+		let mut world = World::new(Field::from_str(
+			"size:5,5; plot_size:10,10 ;;
+			5,5 keep:user;
+			2,1 raider;
+			15,4 keep:user;
+			12,3 raider;"
+		).unwrap());
+		world.update(&[
+			parse_commands("user", &[
+				"@0,0 2,1 move 9,9",
+				"@1,0 2,3 move 4,4",
+				"@1,0 2,1 build stockpile",
+				"@0,0 12,1 build stockpile",
+			]),
+		]);
+		assert_eq!(world.field, Field::from_str(
+			"size:5,5; plot_size:10,10 ;;
+			5,5 keep:user;
+			9,9 raider;
+			15,4 keep:user;
+			14,4 raider;
+			12,1 stockpile;"
+		).unwrap());
+	}
+	
+	#[test]
+	fn test_for_each(){
+		let mut world = World::new(Field::from_str(
+			"size:5,5; plot_size:10,10 ;;
+			5,5 keep:user;
+			0,1 woodcutter;
+			0,2 woodcutter;
+			0,3 woodcutter;
+			1,0 forest;
+			9,1 stockpile;
+			9,2 stockpile;
+			9,3 stockpile;"
+		).unwrap());
+		world.update(&[
+			parse_commands("user", &[
+				"use all woodcutter in plot 0,0",
+				"1,1 build stockpile",
+				"1,2 build stockpile",
+				"1,3 build stockpile",
+				"1,4 build stockpile",
+				"1,5 build stockpile",
+				"1,6 build stockpile",
+				"1,7 build stockpile",
+				"1,8 build stockpile",
+			]),
+		]);
+		// the action limit applies after expansion so the last stockpile is not built
+		assert_eq!(world.field, Field::from_str(
+			"size:5,5; plot_size:10,10 ;;
+			5,5 keep:user;
+			0,1 woodcutter;
+			0,2 woodcutter;
+			0,3 woodcutter;
+			1,0 forest;
+			9,1 stockpile:wood;
+			9,2 stockpile:wood;
+			9,3 stockpile:wood;
+			1,1 stockpile;
+			1,2 stockpile;
+			1,3 stockpile;
+			1,4 stockpile;
+			1,5 stockpile;
+			1,6 stockpile;
+			1,7 stockpile;"
+		).unwrap());
+	}
+	
+	#[test]
+	fn test_standing_orders(){
+		let mut world = World::new(Field::from_str(
+			"size:5,5; plot_size:10,10 ;;
+			5,5 keep:user;
+			0,5 woodcutter;
+			1,5 stockpile;
+			3,3 farm;"
+		).unwrap());
+		let user = UserId("user".to_string());
+		let report = world.update_with_standing_orders(
+			&[parse_commands("user", &["0,5 use"])],
+			&[parse_commands("user", &["0,5 use", "3,3 use", "2,2 build stockpile", "7,7 attack north"])]
+		);
+		assert_eq!(report.messages(&user), &[
+			"Standing order '0,5 use' failed: Tile 0,5 was already used this turn".to_string(),
+			"Standing order '3,3 use' failed: No room to store the resource".to_string(),
+			"Standing order '7,7 attack north' failed: Tile 7,7 is empty".to_string(),
+		]);
+		assert_eq!(world.field, Field::from_str(
+			"size:5,5; plot_size:10,10 ;;
+			5,5 keep:user;
+			0,5 woodcutter;
+			1,5 stockpile:wood;
+			3,3 farm;
+			2,2 stockpile;"
+		).unwrap());
+	}
+	
+	#[test]
+	fn test_conditions(){
+		let mut world = World::new(Field::from_str(
+			"size:5,5; plot_size:10,10 ;;
+			5,5 keep:user;
+			3,3 raider;
+			3,4 raider;
+			9,5 raider;
+			9,6 raider;
+			15,4 keep:other;
+			12,5 raider;
+			13,5 farm;"
+		).unwrap());
+		let user = UserId("user".to_string());
+		let report = world.update(&[
+			parse_commands("other", &[
+				"12,5 move 12,6",
+			]),
+			parse_commands("user", &[
+				"if empty 4,4: 3,3 move 4,4",
+				"if empty 4,4: 3,4 move 4,4",
+				"if enemy units in plot 1,0: 9,5 attack east",
+				"if not enemy units in plot 1,0: 9,6 attack east",
+			]),
+		]);
+		assert_eq!(report.messages(&user), &[
+			"Command 'if empty 4,4: 3,4 move 4,4' failed: Condition 'empty 4,4' does not hold".to_string(),
+			"Command 'if not enemy units in plot 1,0: 9,6 attack east' failed: Condition 'not enemy units in plot 1,0' does not hold".to_string(),
+		]);
+		assert_eq!(world.field, Field::from_str(
+			"size:5,5; plot_size:10,10 ;;
+			5,5 keep:user;
+			4,4 raider;
+			3,4 raider;
+			9,5 raider;
+			9,6 raider;
+			15,4 keep:other;
+			12,6 raider;"
+		).unwrap());
+	}
+	
+	#[test]
+	fn test_alliances(){
+		let mut world = World::new(Field::from_str(
+			"size:5,5; plot_size:10,10 ;;
+			5,5 keep:user;
+			8,5 raider;
+			9,3 raider;
+			9,5 road;
+			15,4 keep:other;"
+		).unwrap());
+		let user = UserId("user".to_string());
+		let other = UserId("other".to_string());
+		let report = world.update(&[
+			parse_commands("user", &["ally other", "8,5 move 9,5"]),
+		]);
+		assert_eq!(report.messages(&user), &["Command '8,5 move 9,5' failed: Can not move to 9,5".to_string()]);
+		assert_eq!(report.messages(&other), &["user wants to form an alliance with you. Write 'ally user' to accept".to_string()]);
+		
+		let report = world.update(&[
+			parse_commands("user", &["8,5 move 9,5", "9,3 attack east"]),
+			parse_commands("other", &["ally user"]),
+		]);
+		assert_eq!(report.messages(&user), &[
+			"You are now allied with other".to_string(),
+			"Command '9,3 attack east' failed: You can not attack an ally".to_string(),
+		]);
+		tileis!(world, 8,5, None);
+		tileis!(world, 10,5, Some(Entity::Raider));
+		
+		let mut world = World::from_str(&world.serialise()).unwrap();
+		assert_eq!(world.turn, 2);
+		assert!(world.alliances.allied(&user, &other));
+		let report = world.update_with_standing_orders(&[], &[parse_commands("user", &["ally other"])]);
+		assert!(report.messages(&user).is_empty());
+		
+		let report = world.update(&[parse_commands("other", &["leave alliance with user"])]);
+		assert_eq!(report.messages(&user), &["other is leaving your alliance, which ends at turn 7".to_string()]);
+		world.update(&[]);
+		world.update(&[]);
+		assert!(world.alliances.allied(&user, &other));
+		let report = world.update(&[parse_commands("user", &["9,3 attack east"])]);
+		assert_eq!(report.messages(&user), &["Your alliance with other has ended".to_string()]);
+		assert!(!world.alliances.allied(&user, &other));
+	}
+	
+	#[test]
+	fn test_trades(){
+		let field = Field::from_str(
+			"size:5,5; plot_size:10,10 ;;
+			5,5 keep:bob;
+			9,5 tradepost;
+			8,4 stockpile:wood;
+			8,5 stockpile:wood;
+			8,6 stockpile:wood;
+			7,5 stockpile;
+			15,4 keep:alice;
+			10,5 tradepost;
+			11,5 stockpile:stone;
+			11,4 stockpile:stone;
+			12,5 stockpile;"
+		).unwrap();
+		let alice = UserId("alice".to_string());
+		let bob = UserId("bob".to_string());
+		
+		let mut world = World::new(field.clone());
+		let report = world.update(&[
+			parse_commands("bob", &["offer 3 wood for 2 stone to alice via 9,5"]),
+			parse_commands("alice", &["accept 3 wood for 1 stone from bob via 10,5"]),
+		]);
+		assert_eq!(report.messages(&alice), &[
+			"Trade 'accept 3 wood for 1 stone from bob via 10,5' failed: There is no matching offer from bob".to_string(),
+			"bob offers 3 wood for 2 stone. Write 'accept 3 wood for 2 stone from bob via 10,5' to accept it in the same turn".to_string(),
+		]);
+		assert_eq!(report.messages(&bob), &["Trade 'offer 3 wood for 2 stone to alice via 9,5' was not accepted".to_string()]);
+		assert_eq!(world.field, field);
+		
+		let report = world.update(&[
+			parse_commands("bob", &["offer 3 wood for 2 stone to alice via 9,5", "9,5 remove"]),
+			parse_commands("alice", &["accept 3 wood for 2 stone from bob via 10,5"]),
+		]);
+		assert_eq!(report.messages(&bob), &[
+			"Trade with alice done: you gave 3 wood and got 2 stone".to_string(),
+			"Command '9,5 remove' failed: Tile 9,5 was already used this turn".to_string(),
+		]);
+		assert_eq!(report.messages(&alice), &["Trade with bob done: you gave 2 stone and got 3 wood".to_string()]);
+		assert_eq!(world.field, Field::from_str(
+			"size:5,5; plot_size:10,10 ;;
+			5,5 keep:bob;
+			9,5 tradepost;
+			8,4 stockpile;
+			8,5 stockpile:stone;
+			8,6 stockpile;
+			7,5 stockpile:stone;
+			15,4 keep:alice;
+			10,5 tradepost;
+			11,5 stockpile:wood;
+			11,4 stockpile:wood;
+			12,5 stockpile:wood;"
+		).unwrap());
+		
+		let report = world.update(&[
+			parse_commands("bob", &["offer 3 wood for 2 stone to alice via 9,5"]),
+			parse_commands("alice", &["accept 3 wood for 2 stone from bob via 10,5"]),
+		]);
+		assert_eq!(report.messages(&bob), &["Trade 'offer 3 wood for 2 stone to alice via 9,5' failed: bob does not have 3 wood".to_string()]);
+		
+		// Trading between two plots of the same player would move resources without using actions
+		let field = Field::from_str(
+			"size:2,1; plot_size:10,10;;
+			5,5 capital:alice; 9,5 tradepost; 8,5 stockpile:wood; 7,5 stockpile;
+			15,4 keep:alice; 10,5 tradepost; 11,5 stockpile:food; 12,5 stockpile;"
+		).unwrap();
+		let mut world = World::new(field.clone());
+		let report = world.update(&[
+			parse_commands("alice", &["offer 1 wood for 1 food to alice via 9,5", "accept 1 wood for 1 food from alice via 10,5"]),
+		]);
+		assert_eq!(report.messages(&alice), &[
+			"Trade 'offer 1 wood for 1 food to alice via 9,5' failed: You can not trade with yourself".to_string(),
+			"Trade 'accept 1 wood for 1 food from alice via 10,5' failed: You can not trade with yourself".to_string(),
+		]);
+		assert_eq!(world.field, field);
+	}
+	
+	#[test]
+	fn test_messages(){
+		let mut world = World::new(Field::from_str("size:5,5; plot_size:10,10 ;;").unwrap());
+		world.turn = 6;
+		let alice = UserId("alice".to_string());
+		let bob = UserId("bob".to_string());
+		let carol = UserId("carol".to_string());
+		let report = world.update(&[
+			parse_commands("bob", &["say alice: let's not fight", "say everyone: peace!"]),
+		]);
+		assert_eq!(report.messages(&alice), &[
+			"Turn 7, message from bob: let's not fight".to_string(),
+			"Turn 7, message from bob to everyone: peace!".to_string(),
+		]);
+		assert_eq!(report.messages(&bob), &[
+			"Turn 7, you said to alice: let's not fight".to_string(),
+			"Turn 7, message from bob to everyone: peace!".to_string(),
+		]);
+		assert_eq!(report.messages(&carol), &["Turn 7, message from bob to everyone: peace!".to_string()]);
+	}
+	
+	#[test]
+	fn test_decay(){
+		let mut world = World::from_str(
+			"size:2,2; plot_size:10,10; turn:0; decay_after:2;;
+			5,5 capital:alice;
+			15,4 keep:alice;
+			6,6 farm;
+			7,7 stockpile:wood;
+			4,15 capital:bob;"
+		).unwrap();
+		let bob = UserId("bob".to_string());
+		// Bob builds something every turn, alice leaves a command file behind that no longer works
+		let bob_builds = |turn: i64| parse_commands("bob", &[&format!("{},11 build stockpile", turn)]);
+		world.update(&[parse_commands("alice", &["6,6 use", "8,8 use"]), bob_builds(1)]);
+		world.update(&[parse_commands("alice", &["8,8 use"]), bob_builds(2)]);
+		let mut world = World::from_str(&world.serialise()).unwrap();
+		assert_eq!(world.activity.last_active(&UserId("alice".to_string())), Some(1));
+		assert_eq!(world.decay_after, Some(2));
+		tileis!(world, 5,5, Some(Entity::Capital(UserId("alice".to_string()))));
+		let report = world.update(&[parse_commands("alice", &["8,8 use"]), bob_builds(3)]);
+		assert!(report.messages(&bob).contains(&"alice has been inactive for 2 turns. Their 2 plots fell into ruin".to_string()));
+		tileis!(world, 5,5, Some(Entity::Ruin));
+		tileis!(world, 15,4, Some(Entity::Ruin));
+		tileis!(world, 6,6, None);
+		tileis!(world, 7,7, Some(Entity::Stockpile(Some(Resource::Wood))));
+		tileis!(world, 4,15, Some(Entity::Capital(bob.clone())));
+		world.update(&[bob_builds(4)]);
+		tileis!(world, 7,7, None);
+		world.update(&[parse_commands("carol", &["0,0 claim"]), bob_builds(5)]);
+		tileis!(world, 5,5, Some(Entity::Capital(UserId("carol".to_string()))));
+	}
+	
+	#[test]
+	fn test_respawn(){
+		let resources: String = ["wood"; 10].iter().chain(["food"; 5].iter()).chain(["stone"; 5].iter()).enumerate()
+			.map(|(i, res)| format!("{},{} stockpile:{}; ", 1 + i % 4, 1 + i / 4, res))
+			.collect();
+		let mut world = World::from_str(&format!(
+			"size:4,1; plot_size:10,10; respawn:cooldown 2, spawn, 2 wood;;
+			5,5 capital:alice;
+			9,5 scoutpost;
+			{}
+			15,4 capital:bob;", resources)
+		).unwrap();
+		let bob = UserId("bob".to_string());
+		let report = world.update(&[parse_commands("alice", &["9,5 use"])]);
+		assert_eq!(report.messages(&bob), &[
+			"bob was eliminated at turn 1".to_string(),
+			"You can claim a new plot from turn 3".to_string()
+		]);
+		tileis!(world, 15,4, Some(Entity::Keep(UserId("alice".to_string()))));
+		
+		let mut world = World::from_str(&world.serialise()).unwrap();
+		assert_eq!(world.eliminated, vec![(bob.clone(), 1)]);
+		let report = world.update(&[parse_commands("bob", &["0,0 claim"])]);
+		assert_eq!(report.messages(&bob), &["Command '0,0 claim' failed: You can claim a new plot from turn 3".to_string()]);
+		// the plot next to the keep of alice can not be claimed, and the new keep counts as used
+		let report = world.update(&[parse_commands("bob", &["0,0 claim", "35,4 use"])]);
+		assert_eq!(report.messages(&bob), &["Command '35,4 use' failed: Tile 35,4 was already used this turn".to_string()]);
+		tileis!(world, 25,5, None);
+		tileis!(world, 35,4, Some(Entity::Capital(bob.clone())));
+		assert_eq!(rules::available_resources(&world.field, Pos::new(35, 4)), ResourceCount::from_vec(&[Resource::Wood, Resource::Wood]));
+		assert!(world.eliminated.is_empty());
+	}
+	
+	#[test]
+	fn test_relocate(){
+		let resources: String = ["wood", "stone", "food"].iter().flat_map(|res| [*res; 5]).enumerate()
+			.map(|(i, res)| format!("{},{} stockpile:{}; ", 1 + i % 4, 1 + i / 4, res))
+			.collect();
+		let mut world = World::from_str(&format!(
+			"size:3,1; plot_size:10,10;;
+			5,5 capital:alice;
+			{}
+			15,4 keep:alice;
+			25,5 keep:alice;", resources)
+		).unwrap();
+		let alice = UserId("alice".to_string());
+		let report = world.update(&[parse_commands("alice", &["5,5 relocate 25,5"])]);
+		assert_eq!(report.messages(&alice), &["Your capital moved from 5,5 to 25,5".to_string()]);
+		tileis!(world, 25,5, Some(Entity::Capital(alice.clone())));
+		tileis!(world, 5,5, Some(Entity::Keep(alice.clone())));
+		assert_eq!(rules::available_resources(&world.field, Pos::new(5, 5)), ResourceCount::default());
+		
+		let mut world = World::from_str(&world.serialise()).unwrap();
+		let report = world.update(&[parse_commands("alice", &["25,5 relocate 15,4"])]);
+		assert_eq!(report.messages(&alice), &[
+			format!("Command '25,5 relocate 15,4' failed: Your capital can not be relocated again before turn {}", 1 + rules::RELOCATION_COOLDOWN)
+		]);
+		world.turn = rules::RELOCATION_COOLDOWN;
+		let report = world.update(&[parse_commands("alice", &["25,5 relocate 15,4", "5,5 relocate 15,4"])]);
+		assert_eq!(report.messages(&alice), &[
+			"Command '25,5 relocate 15,4' failed: Not enough resources".to_string(),
+			"Command '5,5 relocate 15,4' failed: A keep:alice can not be relocated".to_string()
+		]);
+		let report = world.update(&[parse_commands("alice", &["25,5 move 15,4"])]);
+		assert_eq!(report.messages(&alice), &["Command '25,5 move 15,4' failed: A capital:alice can not move".to_string()]);
+	}
+	
+	#[test]
+	fn test_barbarians(){
+		let resources: String = ["wood"; 10].iter().chain(["food"; 5].iter()).chain(["stone"; 5].iter()).enumerate()
+			.map(|(i, res)| format!("{},{} stockpile:{}; ", 1 + i % 4, 1 + i / 4, res))
+			.collect();
+		let mut world = World::from_str(&format!(
+			"size:2,1; plot_size:10,10;;
+			5,5 capital:alice;
+			9,5 scoutpost;
+			7,4 raider;
+			{}", resources)
+		).unwrap();
+		barbarians::seed(&mut world.field, 1, 0);
+		let report = world.update(&[parse_commands("alice", &["9,5 use"])]);
+		assert_eq!(report.messages(&UserId("alice".to_string())), &["Command '9,5 use' failed: The plot of 10,5 is defended".to_string()]);
+		tileis!(world, 7,4, None);
+		
+		for pos in [Pos::new(13, 4), Pos::new(17, 4), Pos::new(15, 2), Pos::new(15, 6)] {
+			world.field.clear_tile(pos);
+		}
+		world.update(&[parse_commands("alice", &["9,5 use"])]);
+		tileis!(world, 15,4, Some(Entity::Keep(UserId("alice".to_string()))));
+		tileis!(world, 17,6, Some(Entity::Stockpile(Some(Resource::Iron))));
+		assert_eq!(scoring::scores(&world.field).len(), 1);
+	}
+	
+	#[test]
+	fn test_victory(){
+		let mut world = World::from_str(
+			"size:2,1; plot_size:10,10; turn:4; victory:plots 100; victory:score after 10;;
+			5,5 capital:alice;
+			9,5 scoutpost;
+			6,6 stockpile:wood;
+			6,7 stockpile:wood;
+			6,8 stockpile:wood;
+			6,9 stockpile:wood;
+			6,4 stockpile:wood;
+			6,3 stockpile:wood;
+			6,2 stockpile:wood;
+			6,1 stockpile:wood;
+			7,1 stockpile:wood;
+			7,2 stockpile:wood;
+			7,3 stockpile:food;
+			7,4 stockpile:food;
+			7,6 stockpile:food;
+			7,7 stockpile:food;
+			7,8 stockpile:food;
+			8,2 stockpile:stone;
+			8,3 stockpile:stone;
+			8,4 stockpile:stone;
+			8,6 stockpile:stone;
+			8,7 stockpile:stone;"
+		).unwrap();
+		let alice = UserId("alice".to_string());
+		let report = world.update(&[parse_commands("alice", &["9,5 use"])]);
+		assert_eq!(report.messages(&alice), &["alice has won the game by holding 2 of 2 plots".to_string()]);
+		assert_eq!(world.winner, Some((alice.clone(), 5)));
+		
+		let mut world = World::from_str(&world.serialise()).unwrap();
+		let field = world.field.clone();
+		assert_eq!(world.victory, vec![VictoryCondition::Plots(100), VictoryCondition::ScoreAfter(10)]);
+		let report = world.update(&[parse_commands("alice", &["6,1 remove"])]);
+		assert_eq!(report.messages(&alice), &["The game is over: alice won at turn 5. No more commands are executed".to_string()]);
+		assert_eq!(world.turn, 5);
+		assert_eq!(world.field, field);
+	}
+	
+	#[test]
+	fn test_takeover_cost(){
+		let mut world = World::from_str(
+			"size:2,1; plot_size:10,10; takeover_cost:2 wood, 1 food;;
+			5,5 capital:alice;
+			9,5 scoutpost;
+			8,8 woodcutter; 9,8 forest;
+			6,6 stockpile:wood; 6,7 stockpile:wood; 6,8 stockpile:food; 6,9 stockpile;
+			15,4 capital:bob;
+			13,8 raider; 12,4 farm;"
+		).unwrap();
+		let alice = UserId("alice".to_string());
+		assert_eq!(World::from_str(&world.serialise()).unwrap().takeover_cost, world.takeover_cost);
+		world.update(&[parse_commands("bob", &["13,8 attack west"]), parse_commands("alice", &["8,8 use"])]);
+		tileis!(world, 8,8, None);
+		world.field.clear_tile(Pos::new(13, 8));
+		world.update(&[parse_commands("alice", &["9,5 use"])]);
+		tileis!(world, 15,4, Some(Entity::Keep(alice.clone())));
+		assert_eq!(rules::available_resources(&world.field, Pos::new(5, 5)), ResourceCount::from_vec(&[Resource::Wood]));
+	}
+	
+	#[test]
+	fn test_upkeep(){
+		let mut world = World::from_str(
+			"size:2,1; plot_size:10,10; upkeep:1;;
+			5,5 capital:alice;
+			6,6 stockpile:food; 6,7 stockpile:food;
+			4,5 raider; 2,5 warrior; 1,1 raider;
+			15,4 keep:barbarians;
+			17,4 warrior;"
+		).unwrap();
+		let alice = UserId("alice".to_string());
+		let report = world.update(&[]);
+		assert_eq!(report.messages(&alice), &["1 units in the plot of 5,5 deserted because there was not enough food".to_string()]);
+		tileis!(world, 4,5, Some(Entity::Raider));
+		tileis!(world, 2,5, Some(Entity::Warrior));
+		tileis!(world, 1,1, None);
+		tileis!(world, 17,4, Some(Entity::Warrior));
+		assert_eq!(rules::available_resources(&world.field, Pos::new(5, 5)), ResourceCount::default());
+		
+		let mut world = World::from_str(&world.serialise()).unwrap();
+		assert_eq!(world.upkeep, Some(1));
+		world.update(&[]);
+		tileis!(world, 4,5, None);
+		tileis!(world, 2,5, None);
+	}
+	
+	#[test]
+	fn test_warehouse(){
+		let mut world = World::from_str(
+			"size:1,1; plot_size:10,10;;
+			5,5 capital:alice;
+			4,5 warehouse:wood,wood,wood,wood,wood,wood,stone,stone;
+			8,8 woodcutter; 9,8 forest;
+			2,2 stockpile:stone;
+			1,1 stockpile:wood;"
+		).unwrap();
+		let alice = UserId("alice".to_string());
+		let report = world.update(&[parse_commands("alice", &["2,2 move 4,5", "8,8 use", "6,6 build warehouse"])]);
+		assert_eq!(report.messages(&alice), &[
+			"Command '2,2 move 4,5' failed: Can not move to 4,5".to_string(),
+			"Command '8,8 use' failed: No room to store the resource".to_string()
+		]);
+		tileis!(world, 4,5, Some(Entity::Warehouse(Vec::new())));
+		tileis!(world, 6,6, Some(Entity::Warehouse(Vec::new())));
+		
+		let mut world = World::from_str(&world.serialise()).unwrap();
+		world.update(&[parse_commands("alice", &["2,2 move 4,5", "8,8 use", "1,1 move 6,6"])]);
+		tileis!(world, 2,2, Some(Entity::Stockpile(None)));
+		tileis!(world, 4,5, Some(Entity::Warehouse(vec![Resource::Stone])));
+		tileis!(world, 6,6, Some(Entity::Warehouse(vec![Resource::Wood, Resource::Wood])));
+		assert_eq!(scoring::scores(&world.field)[0].1.resources, 3);
+		
+		world.update(&[parse_commands("alice", &["3,3 build farm"])]);
+		tileis!(world, 3,3, Some(Entity::Farm));
+		tileis!(world, 6,6, Some(Entity::Warehouse(vec![Resource::Wood])));
+		assert_eq!(rules::free_storage(&world.field, Pos::new(5, 5)), 2 + 7 + 7);
+		
+		// Resources that cross a tradepost arrive in the nearest storage on the other side
+		let mut world = World::from_str(
+			"size:2,1; plot_size:10,10;;
+			5,5 capital:alice; 9,5 tradepost; 8,5 stockpile:food;
+			15,4 keep:alice; 12,4 warehouse:wood;"
+		).unwrap();
+		world.update(&[parse_commands("alice", &["8,5 move 9,5"])]);
+		tileis!(world, 8,5, Some(Entity::Stockpile(None)));
+		tileis!(world, 12,4, Some(Entity::Warehouse(vec![Resource::Wood, Resource::Food])));
+		let report = world.update(&[parse_commands("alice", &["8,5 move 9,5"])]);
+		assert_eq!(report.messages(&alice), &["Command '8,5 move 9,5' failed: A stockpile can not move".to_string()]);
+		
+		
+		// Resources in a warehouse can be moved out again, the first stored one first
+		let mut world = World::from_str(
+			"size:2,1; plot_size:10,10;;
+			5,5 capital:alice; 9,5 tradepost; 7,5 warehouse:wood,food; 6,5 stockpile;
+			15,4 keep:alice; 12,4 stockpile;"
+		).unwrap();
+		world.update(&[parse_commands("alice", &["7,5 move 9,5"])]);
+		tileis!(world, 7,5, Some(Entity::Warehouse(vec![Resource::Food])));
+		tileis!(world, 12,4, Some(Entity::Stockpile(Some(Resource::Wood))));
+		world.update(&[parse_commands("alice", &["7,5 move 6,5"])]);
+		tileis!(world, 7,5, Some(Entity::Warehouse(Vec::new())));
+		tileis!(world, 6,5, Some(Entity::Stockpile(Some(Resource::Food))));
+		let report = world.update(&[parse_commands("alice", &["7,5 move 6,5"])]);
+		assert_eq!(report.messages(&alice), &["Command '7,5 move 6,5' failed: A warehouse can not move".to_string()]);
+	}
+}
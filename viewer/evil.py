@@ -5,6 +5,7 @@ import string
 mapping = {
 	"capital": "@",
 	"keep": "$",
+	"ruin": "#",
 	"constuction": ":",
 	"road": "/",
 	"stockpile": "_",
@@ -12,6 +13,7 @@ mapping = {
 	"stockpile:stone": "*",
 	"stockpile:food": "%",
 	"stockpile:iron": "-",
+	"warehouse": "H",
 	"woodcutter": "W",
 	"farm": "F",
 	"quarry": "Q",
@@ -23,6 +25,7 @@ mapping = {
 	"forest": "%",
 	"swamp": "~",
 	"rock": "^",
+	"wall": "X",
 	None: " "
 }
 
@@ -41,6 +44,8 @@ def map_ent(ent, charmap=mapping):
 		return charmap["keep"]
 	elif ent.startswith("capital:"):
 		return charmap["capital"]
+	elif ent.startswith("warehouse:"):
+		return charmap["warehouse"]
 	elif ent.startswith("construction:"):
 		return charmap["construction"]
 	else:
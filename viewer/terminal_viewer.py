@@ -48,14 +48,16 @@ class Entity:
 		self.actions = actions if actions is not None else []
 
 move = "m: Move"
+relocate = "m: Relocate"
 #use = "p: Use"
 attack = "f: Attack"
 remove = "r: Remove"
 build = "b: Build"
 
 mapping = {
-	"capital": Entity("＠", TextStyle(fg=15, bg=0, bold=True), [move]),
+	"capital": Entity("＠", TextStyle(fg=15, bg=0, bold=True), [relocate]),
 	"keep": Entity("＄", TextStyle(fg=15, bg=0)),
+	"ruin": Entity("＃", TextStyle(fg=8, bg=0)),
 	"constuction": Entity("::"),
 	"road": Entity("//", TextStyle(3,7), [remove]),
 	"tradepost": Entity("TT", TextStyle(3,7), [remove]),
@@ -64,6 +66,7 @@ mapping = {
 	"stockpile:stone": Entity("＊", TextStyle(underscore=True), [move, remove]),
 	"stockpile:food": Entity("88", TextStyle(underscore=True), [move, remove]),
 	"stockpile:iron": Entity("－", TextStyle(underscore=True), [move, remove]),
+	"warehouse": Entity("Ｈ", TextStyle(underscore=True), [remove]),
 	"scoutpost": Entity("Ｓ", actions=["u: Take over", remove]),
 	"woodcutter": Entity("Ｗ", actions=["u: Produce Wood", remove]),
 	"farm": Entity("Ｆ", actions=["u: Produce food", remove]),
@@ -76,6 +79,7 @@ mapping = {
 	"forest": Entity("&&", TextStyle(fg=3, bg=2)),
 	"swamp": Entity("～", TextStyle(fg=4,bg=6)),
 	"rock": Entity("^^", TextStyle(fg=7, bg=8)),
+	"wall": Entity("Ｘ", TextStyle(fg=3, bg=8)),
 	None: Entity(".,", TextStyle(2), actions=[build])
 }
 